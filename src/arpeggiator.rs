@@ -0,0 +1,333 @@
+use crate::note_stack::NoteStack;
+use crate::synth::Synth;
+use crate::ArpMode;
+
+/// Plays the held notes one step at a time in front of `Synth::note_on`/`note_off`. Time is
+/// counted in quarter note beats so it can follow the host's transport.
+pub struct Arpeggiator {
+    pub enabled: bool,
+    pub mode: ArpMode,
+    pub octaves: i32,
+    /// Step length in beats
+    pub rate: f64,
+    /// Portion of the step the note is held for, 0..1
+    pub gate: f64,
+    /// How late every second step is, 1.0 makes it a triplet feel
+    pub swing: f64,
+    pub latch: bool,
+    /// Keys that are physically held down, in the order they were pressed
+    held: NoteStack,
+    /// The notes being arpeggiated, with latch these stay after the keys are let go
+    pattern: NoteStack,
+    /// Notes sounding from the current step, the chord mode plays more than one
+    sounding: [i32; 128],
+    num_sounding: usize,
+    position: f64,
+    /// Grid index of the next step, even and odd steps differ with swing
+    next_step: i64,
+    /// Position in the pattern, starts over when a new pattern starts
+    pattern_step: usize,
+    note_off_at: Option<f64>,
+    random_seed: u32,
+}
+
+impl Arpeggiator {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            mode: ArpMode::Up,
+            octaves: 1,
+            rate: 0.25,
+            gate: 0.5,
+            swing: 0.0,
+            latch: false,
+            held: NoteStack::new(),
+            pattern: NoteStack::new(),
+            sounding: [0; 128],
+            num_sounding: 0,
+            position: 0.0,
+            next_step: 0,
+            pattern_step: 0,
+            note_off_at: None,
+            random_seed: 22222,
+        }
+    }
+
+    pub fn reset(&mut self, synth: &mut Synth) {
+        self.release(synth);
+        self.held.clear();
+        self.pattern.clear();
+        self.position = 0.0;
+        self.next_step = 0;
+        self.pattern_step = 0;
+    }
+
+    /// Follows the host's play position, jumps like loops move the grid along
+    pub fn sync(&mut self, position: f64) {
+        // Small differences are rounding, those shouldn't replay a step
+        let jumped = (position - self.position).abs() > 0.01;
+        self.position = position;
+
+        if jumped {
+            self.next_step = self.step_after(position);
+        }
+    }
+
+    pub fn set_rate(&mut self, rate: f64) {
+        if rate != self.rate {
+            self.rate = rate;
+            self.next_step = self.step_after(self.position);
+        }
+    }
+
+    pub fn set_latch(&mut self, latch: bool) {
+        if self.latch && !latch {
+            // Only the keys that are still held keep playing
+            self.pattern.clear();
+            for (note, velocity) in self.held.notes() {
+                self.pattern.push(*note, *velocity);
+            }
+        }
+
+        self.latch = latch;
+    }
+
+    pub fn note_on(&mut self, note: i32, velocity: f32, transport_playing: bool) {
+        // With latch a new chord replaces the old one once all keys were let go
+        if self.held.is_empty() && self.latch {
+            self.pattern.clear();
+        }
+
+        if self.pattern.is_empty() {
+            self.pattern_step = 0;
+
+            // Without the host's transport the pattern starts right away
+            if !transport_playing {
+                self.position = 0.0;
+                self.next_step = 0;
+            }
+        }
+
+        self.held.push(note, velocity);
+        self.pattern.push(note, velocity);
+    }
+
+    pub fn note_off(&mut self, note: i32) {
+        self.held.remove(note);
+
+        if !self.latch {
+            self.pattern.remove(note);
+        }
+    }
+
+    pub fn advance(&mut self, beats: f64) {
+        self.position += beats;
+    }
+
+    /// Plays the step or releases the notes that are due at the current position
+    pub fn update(&mut self, synth: &mut Synth) {
+        if self.note_off_at.is_some_and(|note_off| self.position >= note_off) {
+            self.release(synth);
+        }
+
+        if self.pattern.is_empty() {
+            // Keeps the grid moving so the next pattern starts on the next step
+            self.release(synth);
+            self.next_step = self.step_after(self.position);
+            return;
+        }
+
+        let step_start = self.step_start(self.next_step);
+        if self.position >= step_start {
+            let step_end = self.step_start(self.next_step + 1);
+
+            self.release(synth);
+            self.play_step(synth);
+            self.note_off_at = Some(step_start + self.gate * (step_end - step_start));
+            self.next_step += 1;
+        }
+    }
+
+    /// How many beats until the next note on or note off, `None` when there is nothing to play
+    pub fn beats_until_next_event(&self) -> Option<f64> {
+        if self.pattern.is_empty() && self.note_off_at.is_none() {
+            return None;
+        }
+
+        let mut next_event = self.step_start(self.next_step);
+
+        if let Some(note_off) = self.note_off_at {
+            next_event = next_event.min(note_off);
+        }
+
+        Some((next_event - self.position).max(0.0))
+    }
+
+    pub fn release(&mut self, synth: &mut Synth) {
+        for note in &self.sounding[..self.num_sounding] {
+            synth.note_off(*note);
+        }
+
+        self.num_sounding = 0;
+        self.note_off_at = None;
+    }
+
+    // Odd steps are pushed back by up to a third of a step with swing
+    fn step_start(&self, step: i64) -> f64 {
+        let swing = if step % 2 != 0 { self.swing / 3.0 } else { 0.0 };
+        (step as f64 + swing) * self.rate
+    }
+
+    fn step_after(&self, position: f64) -> i64 {
+        let step = (position / self.rate).floor() as i64;
+
+        if self.step_start(step) < position {
+            step + 1
+        } else {
+            step
+        }
+    }
+
+    fn play_step(&mut self, synth: &mut Synth) {
+        let mut notes = [(0, 0.0); 128];
+        let len = self.pattern.notes().len();
+        notes[..len].copy_from_slice(self.pattern.notes());
+
+        if self.mode != ArpMode::AsPlayed {
+            notes[..len].sort_unstable_by_key(|(note, _)| *note);
+        }
+
+        let octaves = self.octaves.max(1) as usize;
+        let steps = len * octaves;
+        let step = self.pattern_step;
+        self.pattern_step += 1;
+
+        // Synth::note_on uses these for host voice IDs, the arpeggiator's notes don't have any
+        synth.note_voice_id = None;
+
+        let index = match self.mode {
+            ArpMode::Up | ArpMode::AsPlayed => step % steps,
+            ArpMode::Down => steps - 1 - step % steps,
+            ArpMode::UpDown => {
+                // The top and bottom notes aren't repeated when turning around
+                let cycle = (2 * steps).saturating_sub(2).max(1);
+                let index = step % cycle;
+                if index < steps {
+                    index
+                } else {
+                    cycle - index
+                }
+            }
+            ArpMode::Random => {
+                self.random_seed = self
+                    .random_seed
+                    .wrapping_mul(196314165)
+                    .wrapping_add(907633515);
+                (self.random_seed >> 8) as usize % steps
+            }
+            ArpMode::Chord => {
+                let octave = 12 * (step % octaves) as i32;
+                for (note, velocity) in &notes[..len] {
+                    self.play_note(synth, note + octave, *velocity);
+                }
+                return;
+            }
+        };
+
+        let (note, velocity) = notes[index % len];
+        self.play_note(synth, note + 12 * (index / len) as i32, velocity);
+    }
+
+    fn play_note(&mut self, synth: &mut Synth, note: i32, velocity: f32) {
+        if note > 127 || self.num_sounding == self.sounding.len() {
+            return;
+        }
+
+        synth.note_on(note, velocity);
+        self.sounding[self.num_sounding] = note;
+        self.num_sounding += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plays the steps one after the other and returns the first note of each
+    fn play(mode: ArpMode, octaves: i32, keys: &[i32], steps: usize) -> Vec<Vec<i32>> {
+        let mut synth = Synth::new();
+        synth.tune = 10_000.0;
+        synth.detune = 1.0;
+
+        let mut arp = Arpeggiator::new();
+        arp.mode = mode;
+        arp.octaves = octaves;
+        for key in keys {
+            arp.note_on(*key, 100.0, false);
+        }
+
+        (0..steps)
+            .map(|_| {
+                arp.update(&mut synth);
+                let notes = arp.sounding[..arp.num_sounding].to_vec();
+                arp.advance(arp.rate);
+                notes
+            })
+            .collect()
+    }
+
+    fn single_notes(steps: Vec<Vec<i32>>) -> Vec<i32> {
+        steps
+            .into_iter()
+            .map(|notes| {
+                assert_eq!(notes.len(), 1);
+                notes[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn up_and_down_sort_the_keys() {
+        let up = single_notes(play(ArpMode::Up, 1, &[64, 60, 67], 4));
+        assert_eq!(up, [60, 64, 67, 60]);
+
+        let down = single_notes(play(ArpMode::Down, 1, &[64, 60, 67], 4));
+        assert_eq!(down, [67, 64, 60, 67]);
+    }
+
+    #[test]
+    fn up_down_does_not_repeat_the_turning_notes() {
+        let notes = single_notes(play(ArpMode::UpDown, 1, &[60, 64, 67], 6));
+        assert_eq!(notes, [60, 64, 67, 64, 60, 64]);
+
+        let notes = single_notes(play(ArpMode::UpDown, 1, &[60], 3));
+        assert_eq!(notes, [60, 60, 60]);
+    }
+
+    #[test]
+    fn as_played_keeps_the_key_order() {
+        let notes = single_notes(play(ArpMode::AsPlayed, 1, &[67, 60, 64], 4));
+        assert_eq!(notes, [67, 60, 64, 67]);
+    }
+
+    #[test]
+    fn octaves_are_played_after_the_keys() {
+        let notes = single_notes(play(ArpMode::Up, 2, &[60, 64], 5));
+        assert_eq!(notes, [60, 64, 72, 76, 60]);
+
+        let notes = single_notes(play(ArpMode::Down, 2, &[60, 64], 4));
+        assert_eq!(notes, [76, 72, 64, 60]);
+    }
+
+    #[test]
+    fn random_stays_within_the_pattern() {
+        let notes = single_notes(play(ArpMode::Random, 2, &[60, 64], 32));
+        assert!(notes.iter().all(|note| [60, 64, 72, 76].contains(note)));
+    }
+
+    #[test]
+    fn chord_plays_all_keys_per_octave() {
+        let steps = play(ArpMode::Chord, 2, &[64, 60], 3);
+        assert_eq!(steps, [vec![60, 64], vec![72, 76], vec![60, 64]]);
+    }
+}
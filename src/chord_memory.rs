@@ -0,0 +1,160 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::note_stack::NoteStack;
+
+pub const MAX_CHORD_NOTES: usize = 12;
+
+/// Shared between the editor, which arms learning with its button, and the audio thread, which
+/// captures the chord that is played next.
+pub struct ChordLearn {
+    armed: AtomicBool,
+    /// Bit n is set when the chord has a note n semitones above its lowest note
+    intervals: [AtomicU64; 2],
+    is_learned: AtomicBool,
+    /// Set when learning is armed or cancelled, the audio thread then drops what it captured so far
+    restart_capture: AtomicBool,
+}
+
+impl ChordLearn {
+    pub fn new() -> Self {
+        Self {
+            armed: AtomicBool::new(false),
+            intervals: [AtomicU64::new(0), AtomicU64::new(0)],
+            is_learned: AtomicBool::new(false),
+            restart_capture: AtomicBool::new(false),
+        }
+    }
+
+    pub fn arm(&self) {
+        self.is_learned.store(false, Ordering::Relaxed);
+        self.restart_capture.store(true, Ordering::Relaxed);
+        self.armed.store(true, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.armed.store(false, Ordering::Relaxed);
+        self.restart_capture.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed.load(Ordering::Relaxed)
+    }
+
+    // Called from the audio thread once every key of the chord was let go
+    fn learned(&self, intervals: [u64; 2]) {
+        self.intervals[0].store(intervals[0], Ordering::Relaxed);
+        self.intervals[1].store(intervals[1], Ordering::Relaxed);
+        self.armed.store(false, Ordering::Relaxed);
+        self.is_learned.store(true, Ordering::Release);
+    }
+
+    /// The intervals of the chord in semitones, once one has been captured
+    pub fn take_learned(&self) -> Option<Vec<i32>> {
+        if !self.is_learned.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let intervals = [
+            self.intervals[0].load(Ordering::Relaxed),
+            self.intervals[1].load(Ordering::Relaxed),
+        ];
+
+        Some(
+            (0..128)
+                .filter(|interval| intervals[interval / 64] & (1 << (interval % 64)) != 0)
+                .map(|interval| interval as i32)
+                .take(MAX_CHORD_NOTES)
+                .collect(),
+        )
+    }
+}
+
+/// The audio thread's side of learning, collects every note played until all keys are let go
+pub struct ChordCapture {
+    held: NoteStack,
+    notes: [bool; 128],
+}
+
+impl ChordCapture {
+    pub fn new() -> Self {
+        Self {
+            held: NoteStack::new(),
+            notes: [false; 128],
+        }
+    }
+
+    pub fn note_on(&mut self, note: i32, learn: &ChordLearn) {
+        self.restart(learn);
+        self.held.push(note, 0.0);
+        self.notes[note.clamp(0, 127) as usize] = true;
+    }
+
+    pub fn note_off(&mut self, note: i32, learn: &ChordLearn) {
+        self.restart(learn);
+        self.held.remove(note);
+
+        if !self.held.is_empty() {
+            return;
+        }
+
+        if let Some(root) = self.notes.iter().position(|played| *played) {
+            let mut intervals = [0u64; 2];
+            for (interval, _) in self.notes[root..].iter().enumerate().filter(|(_, played)| **played) {
+                intervals[interval / 64] |= 1 << (interval % 64);
+            }
+
+            learn.learned(intervals);
+        }
+
+        self.notes = [false; 128];
+    }
+
+    // Forgets the keys of a cancelled or earlier learn, so they don't end up in the next chord
+    fn restart(&mut self, learn: &ChordLearn) {
+        if learn.restart_capture.swap(false, Ordering::Relaxed) {
+            self.held.clear();
+            self.notes = [false; 128];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn learns_the_intervals_above_the_lowest_note() {
+        let learn = ChordLearn::new();
+        let mut capture = ChordCapture::new();
+        learn.arm();
+
+        for note in [64, 60, 67] {
+            capture.note_on(note, &learn);
+        }
+        for note in [60, 64, 67] {
+            capture.note_off(note, &learn);
+        }
+
+        assert_eq!(learn.take_learned(), Some(vec![0, 4, 7]));
+        assert!(!learn.is_armed());
+    }
+
+    #[test]
+    fn cancelling_forgets_the_captured_keys() {
+        let learn = ChordLearn::new();
+        let mut capture = ChordCapture::new();
+        learn.arm();
+
+        capture.note_on(48, &learn);
+        capture.note_on(50, &learn);
+        learn.cancel();
+        learn.arm();
+
+        capture.note_on(60, &learn);
+        capture.note_on(63, &learn);
+        capture.note_off(60, &learn);
+        capture.note_off(63, &learn);
+
+        assert_eq!(learn.take_learned(), Some(vec![0, 3]));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::ChorusMode;
+
+// Long enough for the free mode's longest delay at 192 kHz, a power of two so the read position
+// wraps with a mask
+const BUFFER_SIZE: usize = 4096;
+const BUFFER_MASK: usize = BUFFER_SIZE - 1;
+
+/// Stereo chorus modelled on the Juno's BBD chorus. Both sides use the same triangle LFO, the
+/// right one upside down, so the sound spreads out from the center.
+pub struct Chorus {
+    pub sample_rate: f32,
+    pub mode: ChorusMode,
+    /// Free mode LFO rate in Hz
+    pub rate: f32,
+    /// Free mode depth, 0..1
+    pub depth: f32,
+    /// Free mode wet level, 0..1
+    pub mix: f32,
+    buffer: Vec<[f32; 2]>,
+    write_idx: usize,
+    lfo_phase: f32,
+    // Smoothed so switching modes doesn't click
+    delay_center: f32,
+    delay_swing: f32,
+    wet: f32,
+}
+
+impl Chorus {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            mode: ChorusMode::Off,
+            rate: 0.5,
+            depth: 0.5,
+            mix: 0.5,
+            buffer: vec![[0.0; 2]; BUFFER_SIZE],
+            write_idx: 0,
+            lfo_phase: 0.0,
+            delay_center: 3.5,
+            delay_swing: 0.0,
+            wet: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill([0.0; 2]);
+        self.write_idx = 0;
+        self.lfo_phase = 0.0;
+    }
+
+    // LFO rate in Hz, the delay's center and swing in milliseconds and the wet level
+    fn settings(&self) -> (f32, f32, f32, f32) {
+        match self.mode {
+            ChorusMode::Off => (0.5, 3.5, 0.0, 0.0),
+            // The Juno-60's BBD delay sweeps between about 1.7 and 5.4 ms in modes I and II, and
+            // wobbles quickly around 3.5 ms with both buttons down
+            ChorusMode::One => (0.513, 3.5, 1.85, 0.5),
+            ChorusMode::Two => (0.863, 3.5, 1.85, 0.5),
+            ChorusMode::Both => (9.75, 3.5, 0.2, 0.5),
+            ChorusMode::Free => (self.rate, 1.5 + 5.0 * self.depth, 5.0 * self.depth, self.mix),
+        }
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let (rate, delay_center, delay_swing, wet) = self.settings();
+
+        self.delay_center += 0.001 * (delay_center - self.delay_center);
+        self.delay_swing += 0.001 * (delay_swing - self.delay_swing);
+        self.wet += 0.001 * (wet - self.wet);
+
+        self.buffer[self.write_idx] = [left, right];
+
+        if self.wet < 0.0001 && wet == 0.0 {
+            self.write_idx = (self.write_idx + 1) & BUFFER_MASK;
+            return (left, right);
+        }
+
+        self.lfo_phase += rate / self.sample_rate;
+        if self.lfo_phase >= 1.0 {
+            self.lfo_phase -= 1.0;
+        }
+        let triangle = 4.0 * (self.lfo_phase - 0.5).abs() - 1.0;
+
+        let samples_per_ms = 0.001 * self.sample_rate;
+        let delay_left = (self.delay_center + triangle * self.delay_swing) * samples_per_ms;
+        let delay_right = (self.delay_center - triangle * self.delay_swing) * samples_per_ms;
+
+        let wet_left = self.read(delay_left, 0);
+        let wet_right = self.read(delay_right, 1);
+        self.write_idx = (self.write_idx + 1) & BUFFER_MASK;
+
+        let dry = 1.0 - self.wet;
+        (dry * left + self.wet * wet_left, dry * right + self.wet * wet_right)
+    }
+
+    // Reads the delay line with linear interpolation, the delay is in samples
+    fn read(&self, delay: f32, channel: usize) -> f32 {
+        let delay = delay.clamp(1.0, (BUFFER_SIZE - 2) as f32);
+        let whole = delay as usize;
+        let fraction = delay - whole as f32;
+
+        let idx = (self.write_idx + BUFFER_SIZE - whole) & BUFFER_MASK;
+        let previous_idx = (idx + BUFFER_SIZE - 1) & BUFFER_MASK;
+
+        self.buffer[idx][channel] * (1.0 - fraction) + self.buffer[previous_idx][channel] * fraction
+    }
+}
@@ -0,0 +1,114 @@
+use crate::DelayMode;
+
+/// The longest delay time in milliseconds
+pub const MAX_DELAY_MS: f32 = 2000.0;
+
+// One pole filter, the coefficient for a cutoff frequency
+fn one_pole_coefficient(cutoff: f32, sample_rate: f32) -> f32 {
+    1.0 - (-std::f32::consts::TAU * cutoff / sample_rate).exp()
+}
+
+/// Stereo echo. In ping-pong mode the input goes to the left side and every repeat crosses over
+/// to the other side. The feedback path is band limited so the repeats get darker and thinner.
+pub struct Delay {
+    pub sample_rate: f32,
+    pub mode: DelayMode,
+    /// Delay time in milliseconds
+    pub time: f32,
+    /// 0..1, kept below 1 by the parameter so the repeats always die out
+    pub feedback: f32,
+    /// Cutoff frequencies of the high pass and low pass filters in the feedback path, in Hz
+    pub low_cut: f32,
+    pub high_cut: f32,
+    /// 0..1, dry and wet are both at full level at 0.5
+    pub mix: f32,
+    buffer: Vec<[f32; 2]>,
+    write_idx: usize,
+    // Smoothed so time changes glide like a tape delay instead of clicking
+    delay_samples: f32,
+    wet: f32,
+    low_cut_state: [f32; 2],
+    high_cut_state: [f32; 2],
+}
+
+impl Delay {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            mode: DelayMode::Off,
+            time: 500.0,
+            feedback: 0.3,
+            low_cut: 100.0,
+            high_cut: 8000.0,
+            mix: 0.3,
+            buffer: Vec::new(),
+            write_idx: 0,
+            delay_samples: 0.0,
+            wet: 0.0,
+            low_cut_state: [0.0; 2],
+            high_cut_state: [0.0; 2],
+        }
+    }
+
+    /// Allocates the delay line for the sample rate, so processing never has to
+    pub fn initialize(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let len = (MAX_DELAY_MS * 0.001 * sample_rate) as usize + 2;
+        self.buffer = vec![[0.0; 2]; len];
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill([0.0; 2]);
+        self.write_idx = 0;
+        self.delay_samples = self.time * 0.001 * self.sample_rate;
+        self.low_cut_state = [0.0; 2];
+        self.high_cut_state = [0.0; 2];
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let len = self.buffer.len();
+        let wet = if self.mode == DelayMode::Off { 0.0 } else { self.mix };
+        self.wet += 0.001 * (wet - self.wet);
+
+        if len < 2 || (self.wet < 0.0001 && wet == 0.0) {
+            if len > 0 {
+                // Silence, so the old repeats don't come back when the delay is turned on again
+                self.buffer[self.write_idx] = [0.0; 2];
+                self.write_idx = (self.write_idx + 1) % len;
+            }
+            return (left, right);
+        }
+
+        let target = (self.time * 0.001 * self.sample_rate).clamp(1.0, (len - 2) as f32);
+        self.delay_samples += 0.0002 * (target - self.delay_samples);
+
+        let whole = self.delay_samples as usize;
+        let fraction = self.delay_samples - whole as f32;
+        let idx = (self.write_idx + len - whole) % len;
+        let previous_idx = (idx + len - 1) % len;
+        let delayed: [f32; 2] = std::array::from_fn(|channel| {
+            self.buffer[idx][channel] * (1.0 - fraction)
+                + self.buffer[previous_idx][channel] * fraction
+        });
+
+        let low_cut = one_pole_coefficient(self.low_cut, self.sample_rate);
+        let high_cut = one_pole_coefficient(self.high_cut.min(0.45 * self.sample_rate), self.sample_rate);
+        let mut repeats = [0.0; 2];
+        for channel in 0..2 {
+            self.high_cut_state[channel] += high_cut * (delayed[channel] - self.high_cut_state[channel]);
+            self.low_cut_state[channel] += low_cut * (self.high_cut_state[channel] - self.low_cut_state[channel]);
+            repeats[channel] = self.feedback * (self.high_cut_state[channel] - self.low_cut_state[channel]);
+        }
+
+        self.buffer[self.write_idx] = match self.mode {
+            DelayMode::PingPong => [0.5 * (left + right) + repeats[1], repeats[0]],
+            _ => [left + repeats[0], right + repeats[1]],
+        };
+        self.write_idx = (self.write_idx + 1) % len;
+
+        let dry = (2.0 - 2.0 * self.wet).min(1.0);
+        let wet = (2.0 * self.wet).min(1.0);
+        (dry * left + wet * delayed[0], dry * right + wet * delayed[1])
+    }
+}
@@ -0,0 +1,30 @@
+/// Soft clipping overdrive
+pub struct Distortion {
+    /// 0..1
+    pub drive: f32,
+    /// 0..1, how much of the clipped signal replaces the dry one
+    pub mix: f32,
+}
+
+impl Distortion {
+    pub fn new() -> Self {
+        Self {
+            drive: 0.3,
+            mix: 1.0,
+        }
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let gain = 1.0 + 49.0 * self.drive * self.drive;
+        // Quiet signals come out only as much louder as the square root of the gain, so turning
+        // up the drive adds grit more than level
+        let output_gain = 1.0 / gain.sqrt();
+        let shape = |sample: f32| (gain * sample).tanh() * output_gain;
+
+        let dry = 1.0 - self.mix;
+        (
+            dry * left + self.mix * shape(left),
+            dry * right + self.mix * shape(right),
+        )
+    }
+}
@@ -0,0 +1,143 @@
+// Corner frequencies of the shelves in Hz
+const LOW_SHELF_FREQ: f32 = 200.0;
+const HIGH_SHELF_FREQ: f32 = 5000.0;
+const MID_Q: f32 = 0.7;
+
+#[derive(Clone, Copy)]
+enum BiquadType {
+    LowShelf,
+    Peak,
+    HighShelf,
+}
+
+// Transposed direct form II biquad with the Audio EQ Cookbook's coefficients
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: [f32; 2],
+    z2: [f32; 2],
+}
+
+impl Biquad {
+    fn set(&mut self, kind: BiquadType, freq: f32, gain_db: f32, q: f32, sample_rate: f32) {
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let w0 = std::f32::consts::TAU * freq.min(0.45 * sample_rate) / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            BiquadType::Peak => {
+                let alpha = sin / (2.0 * q);
+                (
+                    1.0 + alpha * a,
+                    -2.0 * cos,
+                    1.0 - alpha * a,
+                    1.0 + alpha / a,
+                    -2.0 * cos,
+                    1.0 - alpha / a,
+                )
+            }
+            BiquadType::LowShelf | BiquadType::HighShelf => {
+                // Shelf slope of 1
+                let alpha = sin / 2.0 * 2.0_f32.sqrt();
+                let sqrt_a = 2.0 * a.sqrt() * alpha;
+                let sign = if matches!(kind, BiquadType::LowShelf) { 1.0 } else { -1.0 };
+
+                (
+                    a * ((a + 1.0) - sign * (a - 1.0) * cos + sqrt_a),
+                    sign * 2.0 * a * ((a - 1.0) - sign * (a + 1.0) * cos),
+                    a * ((a + 1.0) - sign * (a - 1.0) * cos - sqrt_a),
+                    (a + 1.0) + sign * (a - 1.0) * cos + sqrt_a,
+                    -sign * 2.0 * ((a - 1.0) + sign * (a + 1.0) * cos),
+                    (a + 1.0) + sign * (a - 1.0) * cos - sqrt_a,
+                )
+            }
+        };
+
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = a1 / a0;
+        self.a2 = a2 / a0;
+    }
+
+    fn reset(&mut self) {
+        self.z1 = [0.0; 2];
+        self.z2 = [0.0; 2];
+    }
+
+    fn process(&mut self, input: f32, channel: usize) -> f32 {
+        let output = self.b0 * input + self.z1[channel];
+        self.z1[channel] = self.b1 * input - self.a1 * output + self.z2[channel];
+        self.z2[channel] = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+/// Three band EQ, low and high shelves and a sweepable mid band
+pub struct Equalizer {
+    pub sample_rate: f32,
+    /// Band gains in dB
+    pub low_gain: f32,
+    pub mid_gain: f32,
+    pub high_gain: f32,
+    /// Center of the mid band in Hz
+    pub mid_freq: f32,
+    bands: [Biquad; 3],
+    // The settings the coefficients were calculated for
+    settings: [f32; 5],
+}
+
+impl Equalizer {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            low_gain: 0.0,
+            mid_gain: 0.0,
+            high_gain: 0.0,
+            mid_freq: 1000.0,
+            bands: [Biquad::default(); 3],
+            settings: [f32::NAN; 5],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for band in &mut self.bands {
+            band.reset();
+        }
+    }
+
+    fn update_coefficients(&mut self) {
+        let settings = [
+            self.sample_rate,
+            self.low_gain,
+            self.mid_gain,
+            self.high_gain,
+            self.mid_freq,
+        ];
+        if settings == self.settings {
+            return;
+        }
+
+        self.settings = settings;
+        self.bands[0].set(BiquadType::LowShelf, LOW_SHELF_FREQ, self.low_gain, 0.0, self.sample_rate);
+        self.bands[1].set(BiquadType::Peak, self.mid_freq, self.mid_gain, MID_Q, self.sample_rate);
+        self.bands[2].set(BiquadType::HighShelf, HIGH_SHELF_FREQ, self.high_gain, 0.0, self.sample_rate);
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        self.update_coefficients();
+
+        let mut output = [left, right];
+        for (channel, sample) in output.iter_mut().enumerate() {
+            for band in &mut self.bands {
+                *sample = band.process(*sample, channel);
+            }
+        }
+
+        (output[0], output[1])
+    }
+}
@@ -0,0 +1,146 @@
+use nih_plug::prelude::Enum;
+
+use crate::chorus::Chorus;
+use crate::delay::Delay;
+use crate::distortion::Distortion;
+use crate::equalizer::Equalizer;
+use crate::phaser::Phaser;
+use crate::reverb::Reverb;
+use crate::FxType;
+
+pub const NUM_FX_SLOTS: usize = 6;
+const NUM_FX_TYPES: usize = 7;
+
+/// The order the effects had before they could be moved around, and whether the slot is
+/// bypassed. The reverb starts bypassed because it used to start switched off.
+pub const DEFAULT_SLOTS: [(FxType, bool); NUM_FX_SLOTS] = [
+    (FxType::Chorus, false),
+    (FxType::Delay, false),
+    (FxType::Reverb, true),
+    (FxType::None, false),
+    (FxType::None, false),
+    (FxType::None, false),
+];
+
+/// The effects after the voice mix, in the order of the slots. There is one of each effect, a
+/// slot picks which one runs in its place.
+pub struct FxRack {
+    /// The effect in each slot and whether the slot is bypassed. A bypassed effect stops at once
+    /// and starts over from silence, while the chorus and delay modes' Off fades the effect out.
+    pub slots: [(FxType, bool); NUM_FX_SLOTS],
+    pub distortion: Distortion,
+    pub chorus: Chorus,
+    pub phaser: Phaser,
+    pub delay: Delay,
+    pub reverb: Reverb,
+    pub equalizer: Equalizer,
+    /// Which effects ran on the last sample. One that starts running again starts from silence
+    /// instead of playing the tail it had when it stopped.
+    running: [bool; NUM_FX_TYPES],
+}
+
+impl FxRack {
+    pub fn new() -> Self {
+        Self {
+            slots: DEFAULT_SLOTS,
+            distortion: Distortion::new(),
+            chorus: Chorus::new(),
+            phaser: Phaser::new(),
+            delay: Delay::new(),
+            reverb: Reverb::new(),
+            equalizer: Equalizer::new(),
+            running: [false; NUM_FX_TYPES],
+        }
+    }
+
+    /// Allocates the delay lines for the sample rate, so processing never has to
+    pub fn initialize(&mut self, sample_rate: f32) {
+        self.chorus.sample_rate = sample_rate;
+        self.phaser.sample_rate = sample_rate;
+        self.equalizer.sample_rate = sample_rate;
+        self.delay.initialize(sample_rate);
+        self.reverb.initialize(sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.chorus.reset();
+        self.phaser.reset();
+        self.delay.reset();
+        self.reverb.reset();
+        self.equalizer.reset();
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let mut output = (left, right);
+        // The same effect in two slots only runs in the first one
+        let mut used = [false; NUM_FX_TYPES];
+
+        for (fx_type, bypass) in self.slots {
+            if bypass || used[fx_type.to_index()] {
+                continue;
+            }
+            used[fx_type.to_index()] = true;
+
+            if !self.running[fx_type.to_index()] {
+                self.reset_effect(fx_type);
+            }
+
+            let (left, right) = output;
+            output = match fx_type {
+                FxType::None => output,
+                FxType::Distortion => self.distortion.process(left, right),
+                FxType::Chorus => self.chorus.process(left, right),
+                FxType::Phaser => self.phaser.process(left, right),
+                FxType::Delay => self.delay.process(left, right),
+                FxType::Reverb => self.reverb.process(left, right),
+                FxType::Equalizer => self.equalizer.process(left, right),
+            };
+        }
+
+        self.running = used;
+        output
+    }
+
+    fn reset_effect(&mut self, fx_type: FxType) {
+        match fx_type {
+            FxType::None | FxType::Distortion => (),
+            FxType::Chorus => self.chorus.reset(),
+            FxType::Phaser => self.phaser.reset(),
+            FxType::Delay => self.delay.reset(),
+            FxType::Reverb => self.reverb.reset(),
+            FxType::Equalizer => self.equalizer.reset(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DelayMode;
+
+    #[test]
+    fn unbypassed_effects_start_from_silence() {
+        let mut fx_rack = FxRack::new();
+        fx_rack.initialize(1000.0);
+        fx_rack.slots = [(FxType::None, false); NUM_FX_SLOTS];
+        fx_rack.slots[0] = (FxType::Delay, false);
+        fx_rack.delay.mode = DelayMode::Stereo;
+        fx_rack.delay.feedback = 0.0;
+
+        // Lets the wet level settle before the delay gets something to repeat
+        for _ in 0..10_000 {
+            fx_rack.process(0.0, 0.0);
+        }
+        fx_rack.process(1.0, 1.0);
+
+        fx_rack.slots[0].1 = true;
+        fx_rack.process(0.0, 0.0);
+        fx_rack.slots[0].1 = false;
+
+        // The repeat would come 500 ms after the input
+        for _ in 0..1000 {
+            let (left, right) = fx_rack.process(0.0, 0.0);
+            assert_eq!((left, right), (0.0, 0.0));
+        }
+    }
+}
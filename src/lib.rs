@@ -1,30 +1,65 @@
 use nih_plug::midi::control_change::{
-    ALL_NOTES_OFF, ALL_SOUND_OFF, MAIN_VOLUME_MSB, MODULATION_MSB,
+    ALL_NOTES_OFF, ALL_SOUND_OFF, BANK_SELECT_LSB, BANK_SELECT_MSB, EXPRESSION_CONTROLLER_MSB, MAIN_VOLUME_MSB, MODULATION_MSB,
+    PAN_MSB,
 };
 use nih_plug::prelude::*;
 use nih_plug_egui::{create_egui_editor, egui, EguiState};
 use tracing_subscriber::prelude::*;
 
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 
+mod arpeggiator;
+mod chord_memory;
+mod chorus;
+mod delay;
+mod distortion;
 mod envelope;
+mod equalizer;
+mod fx_rack;
+mod limiter;
 mod logger;
+mod mts;
+mod midi_map;
 mod noise_generator;
+mod note_stack;
 mod oscillator;
+mod param_changes;
+mod phaser;
 mod presets;
+mod reverb;
 mod state_variable_filter;
 mod synth;
+mod tuning;
 mod rotary_slider;
+mod sequencer;
 mod ui;
 mod voice;
 
+use crate::arpeggiator::Arpeggiator;
+use crate::chord_memory::{ChordCapture, ChordLearn};
+use crate::fx_rack::{DEFAULT_SLOTS, NUM_FX_SLOTS};
 use crate::presets::{Preset, Presets};
+use crate::sequencer::{Sequence, Sequencer};
 use crate::synth::Synth;
+use crate::tuning::Tuning;
 use crate::logger::EventCollector;
-use crate::ui::{rx11_egui_ui, UiView};
+use crate::midi_map::{MidiLearn, MidiMapping};
+use crate::mts::MtsMessage;
+use crate::param_changes::{ParamChanges, ParamChangesEditor};
+use crate::ui::{load_preset, rx11_egui_ui, UiView};
 
 const MAX_BLOCK_SIZE: usize = 64;
 
+// CLAP polyphonic modulation IDs, also used to index `Voice::poly_mod`
+pub const FILTER_FREQ_POLY_MOD_ID: u32 = 0;
+pub const FILTER_RESO_POLY_MOD_ID: u32 = 1;
+pub const OSC_MIX_POLY_MOD_ID: u32 = 2;
+pub const OSC_FINE_TUNE_POLY_MOD_ID: u32 = 3;
+pub const PAN_POLY_MOD_ID: u32 = 4;
+pub const NUM_POLY_MOD_PARAMS: usize = 5;
+
 #[derive(Clone, Enum, PartialEq)]
 pub enum PolyMode {
     #[id = "mono"]
@@ -32,6 +67,12 @@ pub enum PolyMode {
 
     #[id = "poly"]
     Poly,
+
+    #[id = "duo"]
+    Duo,
+
+    #[id = "paraphonic"]
+    Paraphonic,
 }
 
 impl PolyMode {
@@ -39,11 +80,15 @@ impl PolyMode {
         match pm {
             PolyMode::Mono => 0.0,
             PolyMode::Poly => 1.0,
+            PolyMode::Duo => 2.0,
+            PolyMode::Paraphonic => 3.0,
         }
     }
 
     pub fn from_f32(i: f32) -> Self {
         match i {
+            3.0 => PolyMode::Paraphonic,
+            2.0 => PolyMode::Duo,
             1.0 => PolyMode::Poly,
             _ => PolyMode::Mono,
         }
@@ -80,20 +125,487 @@ impl GlideMode {
     }
 }
 
+#[derive(Clone, Enum, PartialEq)]
+pub enum GlideType {
+    #[id = "exponential"]
+    Exponential,
+
+    #[id = "time"]
+    #[name = "Constant Time"]
+    ConstantTime,
+
+    #[id = "rate"]
+    #[name = "Constant Rate"]
+    ConstantRate,
+}
+
+impl GlideType {
+    pub fn to_f32(gt: GlideType) -> f32 {
+        match gt {
+            GlideType::Exponential => 0.0,
+            GlideType::ConstantTime => 1.0,
+            GlideType::ConstantRate => 2.0,
+        }
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        match i {
+            2.0 => GlideType::ConstantRate,
+            1.0 => GlideType::ConstantTime,
+            _ => GlideType::Exponential,
+        }
+    }
+}
+
+/// Note lengths for the tempo synced parameters
+#[derive(Clone, Copy, Enum, PartialEq)]
+pub enum NoteDivision {
+    #[id = "1_1"]
+    #[name = "1/1"]
+    Whole,
+
+    #[id = "1_2"]
+    #[name = "1/2"]
+    Half,
+
+    #[id = "1_2d"]
+    #[name = "1/2 D"]
+    HalfDotted,
+
+    #[id = "1_2t"]
+    #[name = "1/2 T"]
+    HalfTriplet,
+
+    #[id = "1_4"]
+    #[name = "1/4"]
+    Quarter,
+
+    #[id = "1_4d"]
+    #[name = "1/4 D"]
+    QuarterDotted,
+
+    #[id = "1_4t"]
+    #[name = "1/4 T"]
+    QuarterTriplet,
+
+    #[id = "1_8"]
+    #[name = "1/8"]
+    Eighth,
+
+    #[id = "1_8d"]
+    #[name = "1/8 D"]
+    EighthDotted,
+
+    #[id = "1_8t"]
+    #[name = "1/8 T"]
+    EighthTriplet,
+
+    #[id = "1_16"]
+    #[name = "1/16"]
+    Sixteenth,
+
+    #[id = "1_16d"]
+    #[name = "1/16 D"]
+    SixteenthDotted,
+
+    #[id = "1_16t"]
+    #[name = "1/16 T"]
+    SixteenthTriplet,
+
+    #[id = "1_32"]
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl NoteDivision {
+    /// Length of the division in quarter note beats
+    pub fn beats(&self) -> f32 {
+        match self {
+            NoteDivision::Whole => 4.0,
+            NoteDivision::Half => 2.0,
+            NoteDivision::HalfDotted => 3.0,
+            NoteDivision::HalfTriplet => 4.0 / 3.0,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::QuarterDotted => 1.5,
+            NoteDivision::QuarterTriplet => 2.0 / 3.0,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::EighthDotted => 0.75,
+            NoteDivision::EighthTriplet => 1.0 / 3.0,
+            NoteDivision::Sixteenth => 0.25,
+            NoteDivision::SixteenthDotted => 0.375,
+            NoteDivision::SixteenthTriplet => 1.0 / 6.0,
+            NoteDivision::ThirtySecond => 0.125,
+        }
+    }
+
+    /// Length of the division in milliseconds at the given tempo
+    pub fn to_ms(&self, tempo: f32) -> f32 {
+        self.beats() * 60_000.0 / tempo
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        Self::from_index((i as usize).min(Self::variants().len() - 1))
+    }
+}
+
+#[derive(Clone, Enum, PartialEq)]
+pub enum MpeZone {
+    #[id = "off"]
+    Off,
+
+    /// Master channel 1, member channels counting up from 2
+    #[id = "lower"]
+    Lower,
+
+    /// Master channel 16, member channels counting down from 15
+    #[id = "upper"]
+    Upper,
+}
+
+impl MpeZone {
+    pub fn to_f32(mz: MpeZone) -> f32 {
+        match mz {
+            MpeZone::Off => 0.0,
+            MpeZone::Lower => 1.0,
+            MpeZone::Upper => 2.0,
+        }
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        match i {
+            2.0 => MpeZone::Upper,
+            1.0 => MpeZone::Lower,
+            _ => MpeZone::Off,
+        }
+    }
+}
+
+#[derive(Clone, Enum, PartialEq)]
+pub enum VoiceStealMode {
+    #[id = "quietest"]
+    Quietest,
+
+    #[id = "oldest"]
+    Oldest,
+
+    #[id = "lowest"]
+    Lowest,
+
+    #[id = "highest"]
+    Highest,
+
+    #[id = "same_note"]
+    #[name = "Same Note"]
+    SameNote,
+
+    #[id = "released"]
+    #[name = "Prefer Released"]
+    Released,
+}
+
+impl VoiceStealMode {
+    pub fn to_f32(vs: VoiceStealMode) -> f32 {
+        match vs {
+            VoiceStealMode::Quietest => 0.0,
+            VoiceStealMode::Oldest => 1.0,
+            VoiceStealMode::Lowest => 2.0,
+            VoiceStealMode::Highest => 3.0,
+            VoiceStealMode::SameNote => 4.0,
+            VoiceStealMode::Released => 5.0,
+        }
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        match i {
+            5.0 => VoiceStealMode::Released,
+            4.0 => VoiceStealMode::SameNote,
+            3.0 => VoiceStealMode::Highest,
+            2.0 => VoiceStealMode::Lowest,
+            1.0 => VoiceStealMode::Oldest,
+            _ => VoiceStealMode::Quietest,
+        }
+    }
+}
+
+#[derive(Clone, Enum, PartialEq)]
+pub enum NotePriority {
+    #[id = "last"]
+    Last,
+
+    #[id = "lowest"]
+    Lowest,
+
+    #[id = "highest"]
+    Highest,
+}
+
+impl NotePriority {
+    pub fn to_f32(np: NotePriority) -> f32 {
+        match np {
+            NotePriority::Last => 0.0,
+            NotePriority::Lowest => 1.0,
+            NotePriority::Highest => 2.0,
+        }
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        match i {
+            2.0 => NotePriority::Highest,
+            1.0 => NotePriority::Lowest,
+            _ => NotePriority::Last,
+        }
+    }
+}
+
+#[derive(Clone, Enum, PartialEq)]
+pub enum ChorusMode {
+    /// Fades the chorus out but keeps it in its FX slot, unlike bypassing the slot. Either one
+    /// silences the chorus, so it is only heard when it is on and its slot isn't bypassed.
+    #[id = "off"]
+    Off,
+
+    #[id = "i"]
+    #[name = "I"]
+    One,
+
+    #[id = "ii"]
+    #[name = "II"]
+    Two,
+
+    #[id = "i_ii"]
+    #[name = "I+II"]
+    Both,
+
+    /// Rate, depth and mix from the parameters
+    #[id = "free"]
+    Free,
+}
+
+impl ChorusMode {
+    pub fn to_f32(cm: ChorusMode) -> f32 {
+        match cm {
+            ChorusMode::Off => 0.0,
+            ChorusMode::One => 1.0,
+            ChorusMode::Two => 2.0,
+            ChorusMode::Both => 3.0,
+            ChorusMode::Free => 4.0,
+        }
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        match i {
+            4.0 => ChorusMode::Free,
+            3.0 => ChorusMode::Both,
+            2.0 => ChorusMode::Two,
+            1.0 => ChorusMode::One,
+            _ => ChorusMode::Off,
+        }
+    }
+}
+
+#[derive(Clone, Enum, PartialEq)]
+pub enum DelayMode {
+    /// Fades the delay out and clears its repeats but keeps it in its FX slot, like the chorus
+    #[id = "off"]
+    Off,
+
+    #[id = "stereo"]
+    Stereo,
+
+    /// The repeats bounce between the left and right side
+    #[id = "ping_pong"]
+    #[name = "Ping-Pong"]
+    PingPong,
+}
+
+impl DelayMode {
+    pub fn to_f32(dm: DelayMode) -> f32 {
+        match dm {
+            DelayMode::Off => 0.0,
+            DelayMode::Stereo => 1.0,
+            DelayMode::PingPong => 2.0,
+        }
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        match i {
+            2.0 => DelayMode::PingPong,
+            1.0 => DelayMode::Stereo,
+            _ => DelayMode::Off,
+        }
+    }
+}
+
+/// How the pan spread picks the side for each new voice
+#[derive(Clone, Copy, Enum, PartialEq)]
+pub enum PanSpreadMode {
+    /// Every other note goes to the other side
+    #[id = "alternate"]
+    Alternate,
+
+    #[id = "random"]
+    Random,
+}
+
+impl PanSpreadMode {
+    pub fn to_f32(pm: PanSpreadMode) -> f32 {
+        match pm {
+            PanSpreadMode::Alternate => 0.0,
+            PanSpreadMode::Random => 1.0,
+        }
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        match i {
+            1.0 => PanSpreadMode::Random,
+            _ => PanSpreadMode::Alternate,
+        }
+    }
+}
+
+/// The effects an FX rack slot can hold
+#[derive(Clone, Copy, Enum, PartialEq)]
+pub enum FxType {
+    #[id = "none"]
+    None,
+
+    #[id = "distortion"]
+    Distortion,
+
+    #[id = "chorus"]
+    Chorus,
+
+    #[id = "phaser"]
+    Phaser,
+
+    #[id = "delay"]
+    Delay,
+
+    #[id = "reverb"]
+    Reverb,
+
+    #[id = "eq"]
+    #[name = "EQ"]
+    Equalizer,
+}
+
+impl FxType {
+    pub fn to_f32(ft: FxType) -> f32 {
+        match ft {
+            FxType::None => 0.0,
+            FxType::Distortion => 1.0,
+            FxType::Chorus => 2.0,
+            FxType::Phaser => 3.0,
+            FxType::Delay => 4.0,
+            FxType::Reverb => 5.0,
+            FxType::Equalizer => 6.0,
+        }
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        match i {
+            6.0 => FxType::Equalizer,
+            5.0 => FxType::Reverb,
+            4.0 => FxType::Delay,
+            3.0 => FxType::Phaser,
+            2.0 => FxType::Chorus,
+            1.0 => FxType::Distortion,
+            _ => FxType::None,
+        }
+    }
+}
+
+/// The order the arpeggiator plays the held notes in
+#[derive(Clone, Enum, PartialEq)]
+pub enum ArpMode {
+    #[id = "up"]
+    Up,
+
+    #[id = "down"]
+    Down,
+
+    #[id = "up_down"]
+    #[name = "Up/Down"]
+    UpDown,
+
+    #[id = "random"]
+    Random,
+
+    #[id = "as_played"]
+    #[name = "As Played"]
+    AsPlayed,
+
+    /// Every held note at once
+    #[id = "chord"]
+    Chord,
+}
+
+impl ArpMode {
+    pub fn to_f32(am: ArpMode) -> f32 {
+        match am {
+            ArpMode::Up => 0.0,
+            ArpMode::Down => 1.0,
+            ArpMode::UpDown => 2.0,
+            ArpMode::Random => 3.0,
+            ArpMode::AsPlayed => 4.0,
+            ArpMode::Chord => 5.0,
+        }
+    }
+
+    pub fn from_f32(i: f32) -> Self {
+        match i {
+            5.0 => ArpMode::Chord,
+            4.0 => ArpMode::AsPlayed,
+            3.0 => ArpMode::Random,
+            2.0 => ArpMode::UpDown,
+            1.0 => ArpMode::Down,
+            _ => ArpMode::Up,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct UiState {
     pub selected_preset: String,
     pub loaded_preset_on_startup: bool,
     pub _current_view: UiView,
     pub show_debug: bool,
+    pub show_midi_map: bool,
+    pub show_tuning: bool,
+    pub show_sequencer: bool,
+    pub show_fx_rack: bool,
+    pub scale_path: String,
+    pub keyboard_map_path: String,
+    pub tuning_error: Option<String>,
+}
+
+/// Work the audio thread hands off to the GUI thread
+pub enum Task {
+    /// Applies the values queued in `ParamChanges` to the parameters
+    ApplyParamChanges,
 }
 
 pub struct RX11 {
     params: Arc<RX11Params>,
     synth: Synth,
+    arp: Arpeggiator,
+    seq: Sequencer,
     presets: Presets,
     ui_state: UiState,
     logs: EventCollector,
+    midi_learn: Arc<MidiLearn>,
+    chord_learn: Arc<ChordLearn>,
+    chord_capture: ChordCapture,
+    /// Values from mapped CCs and program changes, waiting to be applied to the parameters
+    param_changes: Arc<ParamChanges>,
+    /// Bank select MSB and LSB, each bank holds 128 presets
+    bank: [u8; 2],
+    /// Preset index from a program change, loaded once the output has faded out
+    pending_program: Option<usize>,
+    /// The output level from a CC or program change the host hasn't applied yet
+    pending_output_level: Option<f32>,
+    /// The last program change for the editor to show, -1 when there is none
+    program_change: Arc<AtomicI32>,
 }
 
 impl Default for RX11 {
@@ -103,22 +615,61 @@ impl Default for RX11 {
             .with(collector.clone())
             .init();
         
+        let params = Arc::new(RX11Params::default());
+        let param_changes = Arc::new(ParamChanges::new(params.as_ref()));
+
         Self {
-            params: Arc::new(RX11Params::default()),
+            params,
             synth: Synth::new(),
+            arp: Arpeggiator::new(),
+            seq: Sequencer::new(),
             presets: Presets::init(),
             ui_state: UiState { 
                 selected_preset: "Init".into(), 
                 loaded_preset_on_startup: false,
                 _current_view: UiView::Synth,
                 show_debug: false,
+                show_midi_map: false,
+                show_tuning: false,
+                show_sequencer: false,
+                show_fx_rack: false,
+                scale_path: String::new(),
+                keyboard_map_path: String::new(),
+                tuning_error: None,
             },
             logs: collector,
+            midi_learn: Arc::new(MidiLearn::new()),
+            chord_learn: Arc::new(ChordLearn::new()),
+            chord_capture: ChordCapture::new(),
+            param_changes,
+            bank: [0, 0],
+            pending_program: None,
+            pending_output_level: None,
+            program_change: Arc::new(AtomicI32::new(-1)),
         }
     }
 }
 
 
+/// One slot of the FX rack, the slots are numbered from 1 in their IDs
+#[derive(Params)]
+pub struct FxSlotParams {
+    #[id = "fx_type"]
+    pub fx_type: EnumParam<FxType>,
+
+    #[id = "fx_bypass"]
+    pub bypass: BoolParam,
+}
+
+impl FxSlotParams {
+    fn new(idx: usize) -> Self {
+        Self {
+            fx_type: EnumParam::new(format!("FX Slot {}", idx + 1), DEFAULT_SLOTS[idx].0),
+            bypass: BoolParam::new(format!("FX Slot {} Bypass", idx + 1), DEFAULT_SLOTS[idx].1),
+        }
+    }
+}
+
 #[derive(Params)]
 pub struct RX11Params {
     /// The editor state, saved together with the parameter state so the custom scaling can be
@@ -126,6 +677,22 @@ pub struct RX11Params {
     #[persist = "editor-state"]
     editor_state: Arc<EguiState>,
 
+    /// CC to parameter bindings made with MIDI learn
+    #[persist = "midi-map"]
+    pub midi_map: Arc<RwLock<Vec<MidiMapping>>>,
+
+    /// Scala microtuning, 12-TET when there is none
+    #[persist = "scala-tuning"]
+    pub scala_tuning: Arc<RwLock<Option<Tuning>>>,
+
+    /// The step sequencer's notes
+    #[persist = "sequence"]
+    pub sequence: Arc<RwLock<Sequence>>,
+
+    /// Chord memory intervals in semitones above the key
+    #[persist = "chord"]
+    pub chord: Arc<RwLock<Vec<i32>>>,
+
     #[id = "osc_mix"]
     pub osc_mix: FloatParam,
 
@@ -141,12 +708,36 @@ pub struct RX11Params {
     #[id = "poly_mode"]
     pub poly_mode: EnumParam<PolyMode>,
 
+    #[id = "voice_steal"]
+    pub voice_steal: EnumParam<VoiceStealMode>,
+
+    #[id = "polyphony"]
+    pub polyphony: IntParam,
+
+    #[id = "note_priority"]
+    pub note_priority: EnumParam<NotePriority>,
+
+    #[id = "mono_retrigger"]
+    pub mono_retrigger: BoolParam,
+
     #[id = "glide_rate"]
     pub glide_rate: FloatParam,
 
     #[id = "glide_bend"]
     pub glide_bend: FloatParam,
 
+    #[id = "glide_type"]
+    pub glide_type: EnumParam<GlideType>,
+
+    #[id = "glide_time"]
+    pub glide_time: FloatParam,
+
+    #[id = "glide_sync"]
+    pub glide_sync: BoolParam,
+
+    #[id = "glide_division"]
+    pub glide_division: EnumParam<NoteDivision>,
+
     #[id = "filter_freq"]
     pub filter_freq: FloatParam,
 
@@ -203,12 +794,196 @@ pub struct RX11Params {
 
     #[id = "output"]
     pub output_level: FloatParam,
+
+    #[id = "pan"]
+    pub pan: FloatParam,
+
+    #[id = "mod_wheel_vibrato"]
+    pub mod_wheel_vibrato: FloatParam,
+
+    #[id = "mod_wheel_cutoff"]
+    pub mod_wheel_cutoff: FloatParam,
+
+    #[id = "mod_wheel_pwm"]
+    pub mod_wheel_pwm: FloatParam,
+
+    #[id = "aftertouch_vibrato"]
+    pub aftertouch_vibrato: FloatParam,
+
+    #[id = "aftertouch_cutoff"]
+    pub aftertouch_cutoff: FloatParam,
+
+    #[id = "aftertouch_cutoff_lfo"]
+    pub aftertouch_cutoff_lfo: BoolParam,
+
+    #[id = "aftertouch_volume"]
+    pub aftertouch_volume: FloatParam,
+
+    #[id = "bend_up"]
+    pub bend_up: FloatParam,
+
+    #[id = "bend_down"]
+    pub bend_down: FloatParam,
+
+    #[id = "bend_smoothing"]
+    pub bend_smoothing: FloatParam,
+
+    #[id = "mpe_zone"]
+    pub mpe_zone: EnumParam<MpeZone>,
+
+    #[id = "mpe_members"]
+    pub mpe_members: IntParam,
+
+    #[id = "mpe_bend_range"]
+    pub mpe_bend_range: FloatParam,
+
+    #[id = "mpe_timbre_cutoff"]
+    pub mpe_timbre_cutoff: FloatParam,
+
+    #[id = "arp"]
+    pub arp_enabled: BoolParam,
+
+    #[id = "arp_mode"]
+    pub arp_mode: EnumParam<ArpMode>,
+
+    #[id = "arp_octaves"]
+    pub arp_octaves: IntParam,
+
+    #[id = "arp_rate"]
+    pub arp_rate: EnumParam<NoteDivision>,
+
+    #[id = "arp_gate"]
+    pub arp_gate: FloatParam,
+
+    #[id = "arp_swing"]
+    pub arp_swing: FloatParam,
+
+    #[id = "arp_latch"]
+    pub arp_latch: BoolParam,
+
+    #[id = "seq"]
+    pub seq_enabled: BoolParam,
+
+    #[id = "seq_steps"]
+    pub seq_steps: IntParam,
+
+    #[id = "seq_rate"]
+    pub seq_rate: EnumParam<NoteDivision>,
+
+    #[id = "seq_accent"]
+    pub seq_accent: FloatParam,
+
+    #[id = "chord_memory"]
+    pub chord_memory: BoolParam,
+
+    #[id = "chorus_mode"]
+    pub chorus_mode: EnumParam<ChorusMode>,
+
+    #[id = "chorus_rate"]
+    pub chorus_rate: FloatParam,
+
+    #[id = "chorus_depth"]
+    pub chorus_depth: FloatParam,
+
+    #[id = "chorus_mix"]
+    pub chorus_mix: FloatParam,
+
+    #[id = "delay_mode"]
+    pub delay_mode: EnumParam<DelayMode>,
+
+    #[id = "delay_time"]
+    pub delay_time: FloatParam,
+
+    #[id = "delay_sync"]
+    pub delay_sync: BoolParam,
+
+    #[id = "delay_division"]
+    pub delay_division: EnumParam<NoteDivision>,
+
+    #[id = "delay_feedback"]
+    pub delay_feedback: FloatParam,
+
+    #[id = "delay_low_cut"]
+    pub delay_low_cut: FloatParam,
+
+    #[id = "delay_high_cut"]
+    pub delay_high_cut: FloatParam,
+
+    #[id = "delay_mix"]
+    pub delay_mix: FloatParam,
+
+    #[nested(array, group = "FX Slot")]
+    pub fx_slots: [FxSlotParams; NUM_FX_SLOTS],
+
+    #[id = "dist_drive"]
+    pub dist_drive: FloatParam,
+
+    #[id = "dist_mix"]
+    pub dist_mix: FloatParam,
+
+    #[id = "phaser_rate"]
+    pub phaser_rate: FloatParam,
+
+    #[id = "phaser_depth"]
+    pub phaser_depth: FloatParam,
+
+    #[id = "phaser_feedback"]
+    pub phaser_feedback: FloatParam,
+
+    #[id = "phaser_mix"]
+    pub phaser_mix: FloatParam,
+
+    #[id = "eq_low"]
+    pub eq_low: FloatParam,
+
+    #[id = "eq_mid"]
+    pub eq_mid: FloatParam,
+
+    #[id = "eq_mid_freq"]
+    pub eq_mid_freq: FloatParam,
+
+    #[id = "eq_high"]
+    pub eq_high: FloatParam,
+
+    #[id = "reverb_size"]
+    pub reverb_size: FloatParam,
+
+    #[id = "reverb_decay"]
+    pub reverb_decay: FloatParam,
+
+    #[id = "reverb_damping"]
+    pub reverb_damping: FloatParam,
+
+    #[id = "reverb_pre_delay"]
+    pub reverb_pre_delay: FloatParam,
+
+    #[id = "reverb_mix"]
+    pub reverb_mix: FloatParam,
+
+    #[id = "limiter"]
+    pub limiter: BoolParam,
+
+    #[id = "key_pan"]
+    pub key_pan: FloatParam,
+
+    #[id = "pan_spread"]
+    pub pan_spread: FloatParam,
+
+    #[id = "pan_spread_mode"]
+    pub pan_spread_mode: EnumParam<PanSpreadMode>,
+
+    #[id = "stereo_width"]
+    pub stereo_width: FloatParam,
 }
 
 impl Default for RX11Params {
     fn default() -> Self {
         Self {
             editor_state: EguiState::from_size(80, 600),
+            midi_map: Arc::new(RwLock::new(Vec::new())),
+            scala_tuning: Arc::new(RwLock::new(None)),
+            sequence: Arc::new(RwLock::new(Sequence::default())),
+            chord: Arc::new(RwLock::new(Vec::new())),
 
             osc_mix: FloatParam::new(
                 "Osc Mix",
@@ -218,6 +993,7 @@ impl Default for RX11Params {
                     max: 100.0,
                 },
             )
+            .with_poly_modulation_id(OSC_MIX_POLY_MOD_ID)
             .with_value_to_string(Arc::new(|value| {
                 format!("{:.0}:{:.0}", 100.0 - 0.5 * value, 0.5 * value)
             })),
@@ -243,16 +1019,32 @@ impl Default for RX11Params {
                     center: 0.0,
                 },
             )
+            .with_poly_modulation_id(OSC_FINE_TUNE_POLY_MOD_ID)
             .with_step_size(0.1)
             .with_unit("cent")
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
             poly_mode: EnumParam::new("Poly Mode", PolyMode::Poly),
 
-            glide_mode: EnumParam::new("Glide Mode", GlideMode::Off),
+            voice_steal: EnumParam::new("Voice Steal", VoiceStealMode::Quietest),
 
-            glide_rate: FloatParam::new(
-                "Glide Rate",
+            polyphony: IntParam::new(
+                "Polyphony",
+                8,
+                IntRange::Linear {
+                    min: 1,
+                    max: crate::synth::MAX_VOICES as i32,
+                },
+            ),
+
+            note_priority: EnumParam::new("Note Priority", NotePriority::Last),
+
+            mono_retrigger: BoolParam::new("Mono Retrigger", false),
+
+            glide_mode: EnumParam::new("Glide Mode", GlideMode::Off),
+
+            glide_rate: FloatParam::new(
+                "Glide Rate",
                 35.0,
                 FloatRange::Linear {
                     min: 0.0,
@@ -277,6 +1069,25 @@ impl Default for RX11Params {
             .with_unit("semi")
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
+            glide_type: EnumParam::new("Glide Type", GlideType::Exponential),
+
+            glide_time: FloatParam::new(
+                "Glide Time",
+                100.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit("ms")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            glide_sync: BoolParam::new("Glide Sync", false),
+
+            glide_division: EnumParam::new("Glide Division", NoteDivision::Sixteenth),
+
             filter_freq: FloatParam::new(
                 "Filter Freq",
                 100.0,
@@ -285,6 +1096,7 @@ impl Default for RX11Params {
                     max: 100.0,
                 },
             )
+            .with_poly_modulation_id(FILTER_FREQ_POLY_MOD_ID)
             .with_unit("%")
             .with_step_size(0.1)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
@@ -297,6 +1109,7 @@ impl Default for RX11Params {
                     max: 100.0,
                 },
             )
+            .with_poly_modulation_id(FILTER_RESO_POLY_MOD_ID)
             .with_unit("%")
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
@@ -354,9 +1167,527 @@ impl Default for RX11Params {
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            filter_decay: FloatParam::new(
-                "Filter Decay",
-                30.0,
+            filter_decay: FloatParam::new(
+                "Filter Decay",
+                30.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            filter_sustain: FloatParam::new(
+                "Filter Sustain",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            filter_release: FloatParam::new(
+                "Filter Release",
+                25.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            env_attack: FloatParam::new(
+                "Env Attack",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            env_decay: FloatParam::new(
+                "Env Decay",
+                50.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            env_sustain: FloatParam::new(
+                "Env Sustain",
+                100.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            env_release: FloatParam::new(
+                "Env Release",
+                30.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            lfo_rate: FloatParam::new("LFO Rate", 0.81, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("Hz")
+                .with_step_size(0.01)
+                .with_value_to_string(Arc::new(|value| {
+                    format!("{:.2}", (7.0 * value - 4.0).exp())
+                })),
+
+            vibrato: FloatParam::new(
+                "Vibrato",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("Hz")
+            .with_step_size(0.1)
+            .with_value_to_string(Arc::new(|value| {
+                if value < 0.0 {
+                    format!("PWM {:.2}", -value)
+                } else {
+                    format!("{value:.2}")
+                }
+            })),
+
+            octave: FloatParam::new(
+                "Octave",
+                0.0,
+                FloatRange::Linear {
+                    min: -2.0,
+                    max: 2.0,
+                },
+            )
+            .with_unit("''")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            tuning: FloatParam::new(
+                "Tuning",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("cent")
+            .with_step_size(0.1)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            output_level: FloatParam::new(
+                "Output",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(6.0),
+                    factor: FloatRange::gain_skew_factor(util::db_to_gain(-30.0), util::db_to_gain(6.0)),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit("dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            pan: FloatParam::new(
+                "Pan",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_poly_modulation_id(PAN_POLY_MOD_ID)
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            noise_level: FloatParam::new(
+                "Noise",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            mod_wheel_vibrato: FloatParam::new(
+                "Mod Wheel Vibrato",
+                100.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            mod_wheel_cutoff: FloatParam::new(
+                "Mod Wheel Cutoff",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            mod_wheel_pwm: FloatParam::new(
+                "Mod Wheel PWM",
+                100.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            aftertouch_vibrato: FloatParam::new(
+                "Aftertouch Vibrato",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            aftertouch_cutoff: FloatParam::new(
+                "Aftertouch Cutoff",
+                50.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            aftertouch_cutoff_lfo: BoolParam::new("Aftertouch Cutoff LFO", true),
+
+            aftertouch_volume: FloatParam::new(
+                "Aftertouch Volume",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            bend_up: FloatParam::new(
+                "Bend Up",
+                2.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 48.0,
+                },
+            )
+            .with_unit("semi")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            bend_down: FloatParam::new(
+                "Bend Down",
+                2.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 48.0,
+                },
+            )
+            .with_unit("semi")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            bend_smoothing: FloatParam::new(
+                "Bend Smoothing",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 200.0,
+                },
+            )
+            .with_unit("ms")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            mpe_zone: EnumParam::new("MPE Zone", MpeZone::Off),
+
+            mpe_members: IntParam::new(
+                "MPE Member Channels",
+                15,
+                IntRange::Linear {
+                    min: 1,
+                    max: 15,
+                },
+            ),
+
+            mpe_bend_range: FloatParam::new(
+                "MPE Bend Range",
+                48.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 96.0,
+                },
+            )
+            .with_unit("semi")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            mpe_timbre_cutoff: FloatParam::new(
+                "MPE Timbre Cutoff",
+                50.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            arp_enabled: BoolParam::new("Arpeggiator", false),
+
+            arp_mode: EnumParam::new("Arp Mode", ArpMode::Up),
+
+            arp_octaves: IntParam::new("Arp Octaves", 1, IntRange::Linear { min: 1, max: 4 }),
+
+            arp_rate: EnumParam::new("Arp Rate", NoteDivision::Sixteenth),
+
+            arp_gate: FloatParam::new(
+                "Arp Gate",
+                50.0,
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            arp_swing: FloatParam::new(
+                "Arp Swing",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            arp_latch: BoolParam::new("Arp Latch", false),
+
+            seq_enabled: BoolParam::new("Sequencer", false),
+
+            seq_steps: IntParam::new(
+                "Seq Steps",
+                16,
+                IntRange::Linear {
+                    min: 1,
+                    max: crate::sequencer::MAX_STEPS as i32,
+                },
+            ),
+
+            seq_rate: EnumParam::new("Seq Rate", NoteDivision::Sixteenth),
+
+            seq_accent: FloatParam::new(
+                "Seq Accent",
+                50.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            chord_memory: BoolParam::new("Chord Memory", false),
+
+            chorus_mode: EnumParam::new("Chorus", ChorusMode::Off),
+
+            chorus_rate: FloatParam::new(
+                "Chorus Rate",
+                0.5,
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 10.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            chorus_depth: FloatParam::new(
+                "Chorus Depth",
+                50.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            chorus_mix: FloatParam::new(
+                "Chorus Mix",
+                50.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            delay_mode: EnumParam::new("Delay", DelayMode::Off),
+
+            delay_time: FloatParam::new(
+                "Delay Time",
+                300.0,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: crate::delay::MAX_DELAY_MS,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            delay_sync: BoolParam::new("Delay Sync", false),
+
+            delay_division: EnumParam::new("Delay Division", NoteDivision::Eighth),
+
+            delay_feedback: FloatParam::new(
+                "Delay Feedback",
+                30.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 95.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            delay_low_cut: FloatParam::new(
+                "Delay Low Cut",
+                100.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            delay_high_cut: FloatParam::new(
+                "Delay High Cut",
+                8000.0,
+                FloatRange::Skewed {
+                    min: 1000.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            delay_mix: FloatParam::new(
+                "Delay Mix",
+                30.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            fx_slots: std::array::from_fn(FxSlotParams::new),
+
+            dist_drive: FloatParam::new(
+                "Distortion Drive",
+                30.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            dist_mix: FloatParam::new(
+                "Distortion Mix",
+                100.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(1.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            phaser_rate: FloatParam::new(
+                "Phaser Rate",
+                0.5,
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 10.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            phaser_depth: FloatParam::new(
+                "Phaser Depth",
+                70.0,
                 FloatRange::Linear {
                     min: 0.0,
                     max: 100.0,
@@ -366,9 +1697,9 @@ impl Default for RX11Params {
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            filter_sustain: FloatParam::new(
-                "Filter Sustain",
-                0.0,
+            phaser_feedback: FloatParam::new(
+                "Phaser Feedback",
+                50.0,
                 FloatRange::Linear {
                     min: 0.0,
                     max: 100.0,
@@ -378,9 +1709,9 @@ impl Default for RX11Params {
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            filter_release: FloatParam::new(
-                "Filter Release",
-                25.0,
+            phaser_mix: FloatParam::new(
+                "Phaser Mix",
+                50.0,
                 FloatRange::Linear {
                     min: 0.0,
                     max: 100.0,
@@ -390,9 +1721,57 @@ impl Default for RX11Params {
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            env_attack: FloatParam::new(
-                "Env Attack",
+            eq_low: FloatParam::new(
+                "EQ Low",
+                0.0,
+                FloatRange::Linear {
+                    min: -18.0,
+                    max: 18.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1)
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            eq_mid: FloatParam::new(
+                "EQ Mid",
+                0.0,
+                FloatRange::Linear {
+                    min: -18.0,
+                    max: 18.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1)
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            eq_mid_freq: FloatParam::new(
+                "EQ Mid Freq",
+                1000.0,
+                FloatRange::Skewed {
+                    min: 200.0,
+                    max: 8000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            eq_high: FloatParam::new(
+                "EQ High",
                 0.0,
+                FloatRange::Linear {
+                    min: -18.0,
+                    max: 18.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1)
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            reverb_size: FloatParam::new(
+                "Reverb Size",
+                50.0,
                 FloatRange::Linear {
                     min: 0.0,
                     max: 100.0,
@@ -402,8 +1781,20 @@ impl Default for RX11Params {
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            env_decay: FloatParam::new(
-                "Env Decay",
+            reverb_decay: FloatParam::new(
+                "Reverb Decay",
+                2.0,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" s")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            reverb_damping: FloatParam::new(
+                "Reverb Damping",
                 50.0,
                 FloatRange::Linear {
                     min: 0.0,
@@ -414,21 +1805,21 @@ impl Default for RX11Params {
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            env_sustain: FloatParam::new(
-                "Env Sustain",
-                100.0,
+            reverb_pre_delay: FloatParam::new(
+                "Reverb Pre-Delay",
+                10.0,
                 FloatRange::Linear {
                     min: 0.0,
-                    max: 100.0,
+                    max: crate::reverb::MAX_PRE_DELAY_MS,
                 },
             )
-            .with_unit("%")
+            .with_unit(" ms")
             .with_step_size(1.0)
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
 
-            env_release: FloatParam::new(
-                "Env Release",
-                30.0,
+            reverb_mix: FloatParam::new(
+                "Reverb Mix",
+                25.0,
                 FloatRange::Linear {
                     min: 0.0,
                     max: 100.0,
@@ -438,83 +1829,78 @@ impl Default for RX11Params {
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            lfo_rate: FloatParam::new("LFO Rate", 0.81, FloatRange::Linear { min: 0.0, max: 1.0 })
-                .with_unit("Hz")
-                .with_step_size(0.01)
-                .with_value_to_string(Arc::new(|value| {
-                    format!("{:.2}", (7.0 * value - 4.0).exp())
-                })),
+            limiter: BoolParam::new("Limiter", false),
 
-            vibrato: FloatParam::new(
-                "Vibrato",
-                0.0,
+            key_pan: FloatParam::new(
+                "Key Pan",
+                100.0,
                 FloatRange::Linear {
-                    min: -100.0,
+                    min: 0.0,
                     max: 100.0,
                 },
             )
-            .with_unit("Hz")
-            .with_step_size(0.1)
-            .with_value_to_string(Arc::new(|value| {
-                if value < 0.0 {
-                    format!("PWM {:.2}", -value)
-                } else {
-                    format!("{value:.2}")
-                }
-            })),
-
-            octave: FloatParam::new(
-                "Octave",
-                0.0,
-                FloatRange::Linear {
-                    min: -2.0,
-                    max: 2.0,
-                },
-            )
-            .with_unit("''")
+            .with_unit("%")
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            tuning: FloatParam::new(
-                "Tuning",
+            pan_spread: FloatParam::new(
+                "Pan Spread",
                 0.0,
                 FloatRange::Linear {
-                    min: -100.0,
+                    min: 0.0,
                     max: 100.0,
                 },
             )
-            .with_unit("cent")
-            .with_step_size(0.1)
+            .with_unit("%")
+            .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            output_level: FloatParam::new(
-                "Output",
-                util::db_to_gain(0.0),
-                FloatRange::Skewed {
-                    min: util::db_to_gain(-30.0),
-                    max: util::db_to_gain(6.0),
-                    factor: FloatRange::gain_skew_factor(util::db_to_gain(-30.0), util::db_to_gain(6.0)),
-                },
-            )
-            .with_smoother(SmoothingStyle::Logarithmic(50.0))
-            .with_unit("dB")
-            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
-            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            pan_spread_mode: EnumParam::new("Pan Spread Mode", PanSpreadMode::Alternate),
 
-            noise_level: FloatParam::new(
-                "Noise",
-                0.0,
+            stereo_width: FloatParam::new(
+                "Stereo Width",
+                100.0,
                 FloatRange::Linear {
                     min: 0.0,
-                    max: 100.0,
+                    max: 200.0,
                 },
             )
+            .with_unit("%")
             .with_step_size(1.0)
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
         }
     }
 }
 
+impl RX11 {
+    // Parameter values for the audio thread, with the mapped CCs and program changes the host
+    // hasn't applied yet
+    fn value<P: Param>(&self, param: &P) -> P::Plain {
+        self.param_changes.value(param)
+    }
+
+    // Converts the normalized poly modulation offsets of every playing voice into the synth's units
+    fn update_poly_modulation(&mut self) {
+        let params = &self.params;
+
+        for voice in &mut self.synth.voices {
+            if !voice.envelope.is_active() && !voice.gated {
+                continue;
+            }
+
+            let offsets = voice.poly_mod;
+            let modulated = |param: &FloatParam, id: u32| {
+                param.preview_modulated(offsets[id as usize]) - param.value()
+            };
+
+            voice.cutoff_mod = 0.08 * modulated(&params.filter_freq, FILTER_FREQ_POLY_MOD_ID);
+            voice.resonance_mod = 0.03 * modulated(&params.filter_reso, FILTER_RESO_POLY_MOD_ID);
+            voice.osc_mix_mod = modulated(&params.osc_mix, OSC_MIX_POLY_MOD_ID) / 100.0;
+            voice.detune_mod = modulated(&params.osc_fine_tune, OSC_FINE_TUNE_POLY_MOD_ID);
+            voice.pan_mod = modulated(&params.pan, PAN_POLY_MOD_ID) / 100.0;
+        }
+    }
+}
 
 impl Plugin for RX11 {
     const NAME: &'static str = "RX11 Synth";
@@ -539,18 +1925,41 @@ impl Plugin for RX11 {
         },
     ];
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
-    type SysExMessage = ();
-    type BackgroundTask = ();
+    type SysExMessage = MtsMessage;
+    type BackgroundTask = Task;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let param_changes = self.param_changes.clone();
+
+        Box::new(move |task| match task {
+            Task::ApplyParamChanges => param_changes.apply(),
+        })
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        // The delay lines' lengths depend on the sample rate, allocating them here keeps process
+        // allocation free
+        self.synth.fx_rack.initialize(buffer_config.sample_rate);
+
+        true
+    }
+
     fn reset(&mut self) {
         tracing::debug!("plugin reset called");
+        self.arp.reset(&mut self.synth);
+        self.seq.reset(&mut self.synth);
         self.synth.reset(&self.params);
     }
 
@@ -560,15 +1969,40 @@ impl Plugin for RX11 {
         let presets = self.presets.clone();
         let logger = self.logs.clone();
         let egui_state = params.editor_state.clone();
-
-        create_egui_editor(
+        let midi_learn = self.midi_learn.clone();
+        let chord_learn = self.chord_learn.clone();
+        let program_change = self.program_change.clone();
+        let param_ids: Vec<(String, ParamPtr)> = params
+            .param_map()
+            .into_iter()
+            .map(|(id, param, _)| (id, param))
+            .collect();
+
+        let editor = create_egui_editor(
             self.params.editor_state.clone(),
             self.ui_state.clone(),
             |_, _| {},
             move |egui_ctx, setter, state| {
-                rx11_egui_ui(&egui_state, egui_ctx, setter, state, &params, &presets, &logger);
+                rx11_egui_ui(
+                    &egui_state,
+                    egui_ctx,
+                    setter,
+                    state,
+                    &params,
+                    &presets,
+                    &logger,
+                    &midi_learn,
+                    &chord_learn,
+                    &param_ids,
+                    &program_change,
+                );
             },
-        )
+        )?;
+
+        Some(Box::new(ParamChangesEditor {
+            editor,
+            param_changes: self.param_changes.clone(),
+        }))
     }
 
     fn process(
@@ -578,6 +2012,16 @@ impl Plugin for RX11 {
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
         let sample_rate = context.transport().sample_rate;
+        let tempo = context.transport().tempo.unwrap_or(120.0) as f32;
+        let transport_playing = context.transport().playing;
+
+        // The arpeggiator and sequencer follow the host's position while it plays and run on
+        // their own otherwise
+        if let Some(position) = context.transport().pos_beats().filter(|_| transport_playing) {
+            self.arp.sync(position);
+            self.seq.sync(position);
+        }
+        let beats_per_sample = tempo as f64 / 60.0 / sample_rate as f64;
 
         // TODO - discover if there is a way to get the sample_rate in RX11::default
         // voices also rely on default impl, so I'd need to pass it down.
@@ -595,6 +2039,7 @@ impl Plugin for RX11 {
         let mut block_start: usize = 0;
         let mut block_end: usize = MAX_BLOCK_SIZE.min(num_samples);
         let mut next_event = context.next_event(); // Gets the next NoteEvent
+        let mut param_changes_queued = false;
 
         while block_start < num_samples {
             'events: loop {
@@ -604,52 +2049,126 @@ impl Plugin for RX11 {
                         match event {
                             NoteEvent::NoteOn {
                                 timing: _,
-                                voice_id: _,
-                                channel: _,
+                                voice_id,
+                                channel,
                                 note, // values are 0..128, maybe I can store as i8 instead of i32?
                                 velocity, // values are normalized 0..1, multiply by 127 to get back to original range
                             } => {
-                                self.synth.note_on(note.into(), velocity * 127.0);
+                                self.synth.note_channel = channel;
+                                self.synth.note_voice_id = voice_id;
+                                if self.chord_learn.is_armed() {
+                                    self.chord_capture.note_on(note.into(), &self.chord_learn);
+                                }
+
+                                if self.seq.enabled {
+                                    self.seq.note_on(note.into(), velocity * 127.0, transport_playing);
+                                } else if self.arp.enabled {
+                                    self.arp.note_on(note.into(), velocity * 127.0, transport_playing);
+                                } else {
+                                    self.synth.note_on(note.into(), velocity * 127.0);
+                                }
                             }
                             NoteEvent::NoteOff {
                                 timing: _,
                                 voice_id: _,
-                                channel: _,
+                                channel,
                                 note,
                                 velocity: _,
                             } => {
-                                self.synth.note_off(note.into());
+                                self.synth.note_channel = channel;
+                                if self.chord_learn.is_armed() {
+                                    self.chord_capture.note_off(note.into(), &self.chord_learn);
+                                }
+
+                                if self.seq.enabled {
+                                    self.seq.note_off(note.into());
+                                } else if self.arp.enabled {
+                                    self.arp.note_off(note.into());
+                                } else {
+                                    self.synth.note_off(note.into());
+                                }
                             }
                             NoteEvent::MidiPitchBend {
                                 timing: _,
-                                channel: _,
+                                channel,
                                 value, // Normalized 0..1. 0.5 is no pitch bend
                             } => {
-                                // Express pitch bend in semitones
-                                // TODO - Need to turn 0..1 into semitones 0.89..1.12 according to the book
-                                // A value of pitchbend = 1 means the multiplier won't change
-                                // the pitch.
-                                self.synth.pitch_bend = value + 0.5;
+                                if self.synth.is_mpe_member(channel) {
+                                    // MPE member channels bend only their own notes
+                                    self.synth.channel_bend[channel as usize] =
+                                        (2.0 * value - 1.0) * self.synth.mpe_bend_range;
+                                } else {
+                                    // Stored as -1..1, the synth turns this into semitones using
+                                    // the bend ranges and then into a period multiplier.
+                                    self.synth.pitch_bend_amount = 2.0 * value - 1.0;
+                                }
+                            }
+                            NoteEvent::PolyPressure {
+                                timing: _,
+                                voice_id,
+                                channel,
+                                note,
+                                pressure,
+                            } => {
+                                self.synth.poly_pressure(voice_id, channel, note, pressure);
                             }
                             NoteEvent::MidiChannelPressure {
                                 timing: _,
-                                channel: _,
+                                channel,
                                 pressure,
                             } => {
-                                self.synth.pressure = 0.0001 * pressure * pressure;
+                                if self.synth.is_mpe_member(channel) {
+                                    self.synth.channel_pressure[channel as usize] = pressure * pressure;
+                                } else {
+                                    self.synth.pressure = pressure * pressure;
+                                }
                             }
-                            NoteEvent::MidiCC {
+                            NoteEvent::MidiSysEx {
+                                timing: _,
+                                message,
+                            } => {
+                                self.synth.apply_mts(&message);
+                            }
+                            NoteEvent::MidiProgramChange {
                                 timing: _,
                                 channel: _,
+                                program,
+                            } => {
+                                let bank = 128 * self.bank[0] as usize + self.bank[1] as usize;
+                                let program = 128 * bank + program as usize;
+
+                                // Fade out first so the new values don't click
+                                if program < self.presets.0.len() {
+                                    self.pending_program = Some(program);
+                                    self.synth.program_fade_out = true;
+                                }
+                            }
+                            NoteEvent::MidiCC {
+                                timing: _,
+                                channel,
                                 cc,
                                 value, // 0..1. Normally 0..127 for typical midi, but can be mapped back by multiplying by 127.
                                        // The pedals will usually be off for the first half of the range and on for the second half.
                             } => {
+                                self.midi_learn.cc_received(cc);
+
+                                // Skipped while the editor is changing the mappings
+                                if let Some(midi_map) = self.params.midi_map.try_read() {
+                                    for mapping in midi_map.iter().filter(|mapping| mapping.cc == cc) {
+                                        self.param_changes
+                                            .set_normalized(&mapping.param_id, mapping.normalized_value(value));
+                                        param_changes_queued = true;
+                                    }
+                                }
+
                                 if cc == MODULATION_MSB {
-                                    self.synth.mod_wheel = 0.000005 * value;
+                                    self.synth.mod_wheel = value * value;
                                 }
 
-                                if cc == 0x4A {
+                                if cc == 0x4A && self.synth.is_mpe_member(channel) {
+                                    // MPE timbre, centered around 64
+                                    self.synth.channel_timbre[channel as usize] = 2.0 * value - 1.0;
+                                } else if cc == 0x4A {
                                     // Filter inc
                                     self.synth.filter_ctrl = 0.02 * value;
                                 }
@@ -659,20 +2178,42 @@ impl Plugin for RX11 {
                                     self.synth.filter_ctrl = -0.03 * value;
                                 }
 
+                                if cc == BANK_SELECT_MSB {
+                                    self.bank[0] = (value * 127.0).round() as u8;
+                                }
+
+                                if cc == BANK_SELECT_LSB {
+                                    self.bank[1] = (value * 127.0).round() as u8;
+                                }
+
+                                // GM volume and expression curves, 40 * log10(value) dB
                                 if cc == MAIN_VOLUME_MSB {
-                                    // TODO - Figure out how to notify the UI...
-                                    // ie. write to the params Arc.
-                                    let _volume_ctrl = value;
+                                    self.synth.midi_volume = value * value;
+                                }
+
+                                if cc == EXPRESSION_CONTROLLER_MSB {
+                                    self.synth.midi_expression = value * value;
+                                }
+
+                                if cc == PAN_MSB {
+                                    // 64 is the center, 0 and 1 are both hard left
+                                    self.synth.midi_pan = ((127.0 * value - 64.0) / 63.0).clamp(-1.0, 1.0);
                                 }
 
                                 // TODO - unsure of the const for footpedal in nih_plug
                                 if cc == 0x40 {
-                                    self.synth.is_sustained = value >= 0.5;
+                                    // Continuous pedals can be half pressed
+                                    self.synth.set_sustain_pedal(value);
+                                }
 
-                                    if !self.synth.is_sustained {
-                                        // release the sustained voices
-                                        self.synth.note_off(crate::synth::SUSTAIN);
-                                    }
+                                // Sostenuto
+                                if cc == 0x42 {
+                                    self.synth.set_sostenuto_pedal(value >= 0.5);
+                                }
+
+                                // Soft pedal
+                                if cc == 0x43 {
+                                    self.synth.soft_pedal = value;
                                 }
 
                                 // All Notes Off (aka. PANIC!!!) Message
@@ -681,7 +2222,99 @@ impl Plugin for RX11 {
                                         voice.reset();
                                     }
 
+                                    self.synth.held_notes.clear();
+
                                     self.synth.is_sustained = false;
+                                    self.synth.sustain_depth = 0.0;
+                                    self.synth.is_sostenuto = false;
+                                }
+                            }
+                            NoteEvent::Choke {
+                                timing: _,
+                                voice_id,
+                                channel,
+                                note,
+                            } => {
+                                if let Some(voice_idx) =
+                                    self.synth.find_voice(voice_id, channel, note)
+                                {
+                                    self.synth.voices[voice_idx].reset();
+                                }
+                            }
+                            NoteEvent::PolyModulation {
+                                timing: _,
+                                voice_id,
+                                poly_modulation_id,
+                                normalized_offset,
+                            } => {
+                                // The offsets are turned into the voice's modulation for every block
+                                if let Some(voice_idx) = self.synth.find_voice(Some(voice_id), 0, 0) {
+                                    if let Some(offset) = self.synth.voices[voice_idx]
+                                        .poly_mod
+                                        .get_mut(poly_modulation_id as usize)
+                                    {
+                                        *offset = normalized_offset;
+                                    }
+                                }
+                            }
+                            NoteEvent::MonoAutomation { .. } => {
+                                // With sample accurate automation the parameter already has its new
+                                // value at this point, the poly modulation is applied on top of it for
+                                // every block.
+                            }
+                            NoteEvent::PolyTuning {
+                                timing: _,
+                                voice_id,
+                                channel,
+                                note,
+                                tuning,
+                            } => {
+                                if let Some(voice_idx) = self.synth.find_voice(voice_id, channel, note) {
+                                    self.synth.voices[voice_idx].tuning = tuning;
+                                }
+                            }
+                            NoteEvent::PolyBrightness {
+                                timing: _,
+                                voice_id,
+                                channel,
+                                note,
+                                brightness,
+                            } => {
+                                if let Some(voice_idx) = self.synth.find_voice(voice_id, channel, note) {
+                                    self.synth.voices[voice_idx].brightness = 2.0 * brightness - 1.0;
+                                }
+                            }
+                            NoteEvent::PolyVolume {
+                                timing: _,
+                                voice_id,
+                                channel,
+                                note,
+                                gain,
+                            } => {
+                                if let Some(voice_idx) = self.synth.find_voice(voice_id, channel, note) {
+                                    self.synth.voices[voice_idx].note_gain = gain;
+                                }
+                            }
+                            NoteEvent::PolyPan {
+                                timing: _,
+                                voice_id,
+                                channel,
+                                note,
+                                pan,
+                            } => {
+                                if let Some(voice_idx) = self.synth.find_voice(voice_id, channel, note) {
+                                    self.synth.voices[voice_idx].note_pan = pan;
+                                }
+                            }
+                            NoteEvent::PolyVibrato {
+                                timing: _,
+                                voice_id,
+                                channel,
+                                note,
+                                vibrato,
+                            } => {
+                                if let Some(voice_idx) = self.synth.find_voice(voice_id, channel, note) {
+                                    self.synth.voices[voice_idx].vibrato = vibrato;
                                 }
                             }
                             _ => {}
@@ -697,6 +2330,33 @@ impl Plugin for RX11 {
                 }
             }
 
+            // Picks up tunings loaded in the editor or restored with the plugin state
+            if let Some(tuning) = self.params.scala_tuning.try_read() {
+                let generation = tuning.as_ref().map_or(0, |tuning| tuning.generation);
+                if generation != self.synth.tuning_generation {
+                    self.synth.set_tuning(tuning.as_ref());
+                }
+            }
+
+            if let Some(program) = self.pending_program {
+                if self.synth.is_program_faded_out() {
+                    load_preset(&self.presets.0[program], &*self.param_changes, &self.params);
+                    self.program_change.store(program as i32, Ordering::Relaxed);
+                    self.pending_program = None;
+                    param_changes_queued = true;
+                }
+            }
+
+            // The preset's values are read from `param_changes` right away, so it can fade back in
+            self.synth.program_fade_out = self.pending_program.is_some();
+
+            // The parameter smooths the output level itself, but only toward the host's value
+            let output_level = self.param_changes.pending(&self.params.output_level);
+            if let Some(level) = output_level.filter(|_| output_level != self.pending_output_level) {
+                self.params.output_level.smoothed.set_target(sample_rate, level);
+            }
+            self.pending_output_level = output_level;
+
             // Parameter stuff...
             // TODO: Eventually all other params will be here and can potentially be expensive
             // to calculate their values on every process block iteration.
@@ -705,15 +2365,15 @@ impl Plugin for RX11 {
             // Essentially an atomic boolean is used in the JUCE examples which indicates if a parameter changed.
 
             // ADSR Envelope
-            self.synth.env_attack =
-                (-inverse_sample_rate * (5.5 - 0.075 * self.params.env_attack.value()).exp()).exp();
+            let env_attack = self.value(&self.params.env_attack);
+            self.synth.env_attack = (-inverse_sample_rate * (5.5 - 0.075 * env_attack).exp()).exp();
 
-            self.synth.env_decay =
-                (-inverse_sample_rate * (5.5 - 0.075 * self.params.env_decay.value()).exp()).exp();
+            let env_decay = self.value(&self.params.env_decay);
+            self.synth.env_decay = (-inverse_sample_rate * (5.5 - 0.075 * env_decay).exp()).exp();
 
-            self.synth.env_sustain = self.params.env_sustain.value() / 100.0;
+            self.synth.env_sustain = self.value(&self.params.env_sustain) / 100.0;
 
-            let env_release = self.params.env_release.value();
+            let env_release = self.value(&self.params.env_release);
             if env_release < 1.0 {
                 self.synth.env_release = 0.75; // Extra fast release
             } else {
@@ -722,24 +2382,34 @@ impl Plugin for RX11 {
             }
 
             // Voices
-            match self.params.poly_mode.value() {
-                PolyMode::Mono => self.synth.num_voices = 1,
-                PolyMode::Poly => self.synth.num_voices = crate::synth::MAX_VOICES,
+            let poly_mode = self.value(&self.params.poly_mode);
+            match poly_mode {
+                PolyMode::Mono | PolyMode::Duo => self.synth.num_voices = 1,
+                PolyMode::Poly | PolyMode::Paraphonic => self
+                    .synth
+                    .set_polyphony(self.value(&self.params.polyphony) as usize),
             }
+            self.synth.set_poly_mode(poly_mode);
+            self.synth.voice_steal_mode = self.value(&self.params.voice_steal);
+            self.synth.note_priority = self.value(&self.params.note_priority);
+            self.synth.mono_retrigger = self.value(&self.params.mono_retrigger);
+            self.synth.steal_fade_step = inverse_sample_rate / crate::synth::STEAL_FADE_TIME;
 
             // Oscillator Tuning
-            let octave = self.params.octave.value();
-            let tuning = self.params.tuning.value();
+            let octave = self.value(&self.params.octave);
+            let tuning = self.value(&self.params.tuning);
             let tune_in_semi = -36.3763 - 12.0 * octave - tuning / 100.0;
             self.synth.tune = sample_rate * (0.05776226505 * tune_in_semi).exp();
 
-            let semi = self.params.osc_tune.value();
-            let cent = self.params.osc_fine_tune.value();
+            let semi = self.value(&self.params.osc_tune);
+            let cent = self.value(&self.params.osc_fine_tune);
             self.synth.detune = 1.059463094359_f32.powf(-semi - 0.01 * cent); // Total detuning in semitones
-            self.synth.osc_mix = self.params.osc_mix.value() / 100.0;
+            self.synth.osc_mix = self.value(&self.params.osc_mix) / 100.0;
+            self.synth.pan = self.value(&self.params.pan) / 100.0;
+            self.update_poly_modulation();
 
             // Filter
-            let filter_velocity = self.params.filter_velocity.value();
+            let filter_velocity = self.value(&self.params.filter_velocity);
             if filter_velocity < -90.0 {
                 self.synth.velocity_sensitivity = 0.0;
                 self.synth.ignore_velocity = true;
@@ -748,34 +2418,63 @@ impl Plugin for RX11 {
                 self.synth.ignore_velocity = false;
             }
 
-            let filter_lfo = self.params.filter_lfo.value() / 100.0;
+            let filter_lfo = self.value(&self.params.filter_lfo) / 100.0;
             self.synth.filter_lfo_depth = 2.5 * filter_lfo * filter_lfo;
 
             // Convert range from -1.5..6.5
-            self.synth.filter_key_tracking = 0.08 * self.params.filter_freq.value() - 1.5;
+            self.synth.filter_key_tracking = 0.08 * self.value(&self.params.filter_freq) - 1.5;
 
             // Filter Q starts at 1 and goes up to ~20.
-            let filter_resonance = self.params.filter_reso.value() / 100.0;
+            let filter_resonance = self.value(&self.params.filter_reso) / 100.0;
             self.synth.filter_resonance = (3.0 * filter_resonance).exp();
 
             self.synth.filter_attack = (-inverse_update_rate
-                * (5.5 - 0.075 * self.params.filter_attack.value()).exp())
+                * (5.5 - 0.075 * self.value(&self.params.filter_attack)).exp())
             .exp();
             self.synth.filter_decay = (-inverse_update_rate
-                * (5.5 - 0.075 * self.params.filter_decay.value()).exp())
+                * (5.5 - 0.075 * self.value(&self.params.filter_decay)).exp())
             .exp();
             self.synth.filter_release = (-inverse_update_rate
-                * (5.5 - 0.075 * self.params.filter_release.value()).exp())
+                * (5.5 - 0.075 * self.value(&self.params.filter_release)).exp())
             .exp();
-            let filter_sustain = self.params.filter_sustain.value() / 100.0;
+            let filter_sustain = self.value(&self.params.filter_sustain) / 100.0;
             self.synth.filter_sustain = filter_sustain * filter_sustain;
-            self.synth.filter_env_depth = 0.06 * self.params.filter_env.value();
+            self.synth.filter_env_depth = 0.06 * self.value(&self.params.filter_env);
 
             // LFO & Vibrato: Phase increment = 2PI * freq / sample rate
-            let lfo_rate = (7.0 * self.params.lfo_rate.value() - 4.0).exp();
+            let lfo_rate = (7.0 * self.value(&self.params.lfo_rate) - 4.0).exp();
             self.synth.lfo_phase_increment = lfo_rate * inverse_update_rate * std::f32::consts::TAU;
 
-            let vibrato = self.params.vibrato.value() / 200.0;
+            // Pitch Bend
+            self.synth.bend_range_up = self.value(&self.params.bend_up);
+            self.synth.bend_range_down = self.value(&self.params.bend_down);
+            let bend_smoothing = self.value(&self.params.bend_smoothing);
+            if bend_smoothing < 1.0 {
+                self.synth.bend_smoothing = 1.0; // No smoothing
+            } else {
+                self.synth.bend_smoothing =
+                    1.0 - (-inverse_update_rate * 1000.0 / bend_smoothing).exp();
+            }
+
+            // MPE
+            self.synth.set_mpe_zone(
+                self.value(&self.params.mpe_zone),
+                self.value(&self.params.mpe_members) as u8,
+            );
+            self.synth.mpe_bend_range = self.value(&self.params.mpe_bend_range);
+            self.synth.mpe_timbre_cutoff = 0.025 * self.value(&self.params.mpe_timbre_cutoff);
+
+            // Mod wheel & aftertouch routing. The vibrato/PWM depths top out at the same 0.08
+            // the JX11 mod wheel did, the cutoff depths match the Filter LFO range.
+            self.synth.mod_wheel_vibrato = 0.0008 * self.value(&self.params.mod_wheel_vibrato);
+            self.synth.mod_wheel_pwm = 0.0008 * self.value(&self.params.mod_wheel_pwm);
+            self.synth.mod_wheel_cutoff = 0.025 * self.value(&self.params.mod_wheel_cutoff);
+            self.synth.aftertouch_vibrato = 0.0008 * self.value(&self.params.aftertouch_vibrato);
+            self.synth.aftertouch_cutoff = 0.025 * self.value(&self.params.aftertouch_cutoff);
+            self.synth.aftertouch_cutoff_lfo = self.value(&self.params.aftertouch_cutoff_lfo);
+            self.synth.aftertouch_volume = self.value(&self.params.aftertouch_volume) / 100.0;
+
+            let vibrato = self.value(&self.params.vibrato) / 200.0;
             self.synth.vibrato = 0.2 * vibrato * vibrato;
 
             self.synth.pwm_depth = self.synth.vibrato;
@@ -783,8 +2482,8 @@ impl Plugin for RX11 {
                 self.synth.vibrato = 0.0;
             }
 
-            self.synth.glide_mode = self.params.glide_mode.value();
-            let glide_rate = self.params.glide_rate.value();
+            self.synth.glide_mode = self.value(&self.params.glide_mode);
+            let glide_rate = self.value(&self.params.glide_rate);
             if glide_rate < 2.0 {
                 self.synth.glide_rate = 1.0; // No glide
             } else {
@@ -792,8 +2491,140 @@ impl Plugin for RX11 {
                     1.0 - (-inverse_update_rate * (6.0 - 0.07 * glide_rate).exp()).exp();
             }
 
+            // Constant time glides take Glide Time for any interval, constant rate glides take
+            // Glide Time for each octave.
+            self.synth.glide_type = self.value(&self.params.glide_type);
+            let glide_time = if self.value(&self.params.glide_sync) {
+                self.value(&self.params.glide_division).to_ms(tempo)
+            } else {
+                self.value(&self.params.glide_time)
+            };
+            self.synth.glide_updates = glide_time / (1000.0 * inverse_update_rate);
+
+            // Chord memory, while learning the keys play as they are so the new chord can be heard
+            self.synth.chord_enabled =
+                self.value(&self.params.chord_memory) && !self.chord_learn.is_armed();
+            if let Some(chord) = self.params.chord.try_read() {
+                self.synth.set_chord(&chord);
+            }
+
+            // Arpeggiator
+            // The held keys' note offs go somewhere else from now on, so nothing they started
+            // can keep sounding
+            let arp_enabled = self.value(&self.params.arp_enabled);
+            if self.arp.enabled != arp_enabled {
+                self.arp.reset(&mut self.synth);
+                self.synth.release_all();
+            }
+            self.arp.enabled = arp_enabled;
+            self.arp.mode = self.value(&self.params.arp_mode);
+            self.arp.octaves = self.value(&self.params.arp_octaves);
+            self.arp
+                .set_rate(self.value(&self.params.arp_rate).beats() as f64);
+            self.arp.gate = self.value(&self.params.arp_gate) as f64 / 100.0;
+            self.arp.swing = self.value(&self.params.arp_swing) as f64 / 100.0;
+            self.arp
+                .set_latch(self.value(&self.params.arp_latch));
+
+            if self.arp.enabled {
+                self.arp.update(&mut self.synth);
+
+                // The block ends at the arpeggiator's next note so its timing is sample accurate
+                if let Some(beats) = self.arp.beats_until_next_event() {
+                    let samples = (beats / beats_per_sample).ceil() as usize;
+                    block_end = block_end.min(block_start + samples.max(1));
+                }
+            }
+
+            // Step sequencer
+            // Like the arpeggiator, and the sequencer takes the keys from the arpeggiator too
+            let seq_enabled = self.value(&self.params.seq_enabled);
+            if self.seq.enabled != seq_enabled {
+                self.seq.reset(&mut self.synth);
+                self.arp.reset(&mut self.synth);
+                self.synth.release_all();
+            }
+            self.seq.enabled = seq_enabled;
+            self.seq.length = self.value(&self.params.seq_steps) as usize;
+            self.seq
+                .set_rate(self.value(&self.params.seq_rate).beats() as f64);
+            self.seq.accent = 0.03 * self.value(&self.params.seq_accent);
+
+            if self.seq.enabled {
+                // Keeps the last steps if the editor is changing them right now
+                if let Some(sequence) = self.params.sequence.try_read() {
+                    self.seq.set_steps(&sequence);
+                }
+
+                self.seq.update(&mut self.synth, transport_playing);
+
+                if let Some(beats) = self.seq.beats_until_next_event(transport_playing) {
+                    let samples = (beats / beats_per_sample).ceil() as usize;
+                    block_end = block_end.min(block_start + samples.max(1));
+                }
+            }
+
+            // FX rack
+            for (slot, slot_params) in self.synth.fx_rack.slots.iter_mut().zip(&self.params.fx_slots) {
+                *slot = (
+                    self.param_changes.value(&slot_params.fx_type),
+                    self.param_changes.value(&slot_params.bypass),
+                );
+            }
+
+            self.synth.fx_rack.distortion.drive = self.value(&self.params.dist_drive) / 100.0;
+            self.synth.fx_rack.distortion.mix = self.value(&self.params.dist_mix) / 100.0;
+
+            self.synth.fx_rack.phaser.rate = self.value(&self.params.phaser_rate);
+            self.synth.fx_rack.phaser.depth = self.value(&self.params.phaser_depth) / 100.0;
+            self.synth.fx_rack.phaser.feedback = self.value(&self.params.phaser_feedback) / 100.0;
+            self.synth.fx_rack.phaser.mix = self.value(&self.params.phaser_mix) / 100.0;
+
+            self.synth.fx_rack.equalizer.low_gain = self.value(&self.params.eq_low);
+            self.synth.fx_rack.equalizer.mid_gain = self.value(&self.params.eq_mid);
+            self.synth.fx_rack.equalizer.mid_freq = self.value(&self.params.eq_mid_freq);
+            self.synth.fx_rack.equalizer.high_gain = self.value(&self.params.eq_high);
+
+            // Chorus
+            self.synth.fx_rack.chorus.mode = self.value(&self.params.chorus_mode);
+            self.synth.fx_rack.chorus.rate = self.value(&self.params.chorus_rate);
+            self.synth.fx_rack.chorus.depth = self.value(&self.params.chorus_depth) / 100.0;
+            self.synth.fx_rack.chorus.mix = self.value(&self.params.chorus_mix) / 100.0;
+
+            // Delay
+            self.synth.fx_rack.delay.mode = self.value(&self.params.delay_mode);
+            self.synth.fx_rack.delay.time = if self.value(&self.params.delay_sync) {
+                self.params
+                    .delay_division
+                    .value()
+                    .to_ms(tempo)
+                    .min(crate::delay::MAX_DELAY_MS)
+            } else {
+                self.value(&self.params.delay_time)
+            };
+            self.synth.fx_rack.delay.feedback = self.value(&self.params.delay_feedback) / 100.0;
+            self.synth.fx_rack.delay.low_cut = self.value(&self.params.delay_low_cut);
+            self.synth.fx_rack.delay.high_cut = self.value(&self.params.delay_high_cut);
+            self.synth.fx_rack.delay.mix = self.value(&self.params.delay_mix) / 100.0;
+
+            // Reverb
+            self.synth.fx_rack.reverb.size = self.value(&self.params.reverb_size) / 100.0;
+            self.synth.fx_rack.reverb.decay = self.value(&self.params.reverb_decay);
+            self.synth.fx_rack.reverb.damping = self.value(&self.params.reverb_damping) / 100.0;
+            self.synth.fx_rack.reverb.pre_delay = self.value(&self.params.reverb_pre_delay);
+            self.synth.fx_rack.reverb.mix = self.value(&self.params.reverb_mix) / 100.0;
+
+            // Stereo
+            self.synth.key_pan_amount = self.value(&self.params.key_pan) / 100.0;
+            self.synth.pan_spread = self.value(&self.params.pan_spread) / 100.0;
+            self.synth.pan_spread_mode = self.value(&self.params.pan_spread_mode);
+            self.synth.stereo_width = self.value(&self.params.stereo_width) / 100.0;
+
+            // Master bus
+            self.synth.limiter.enabled = self.value(&self.params.limiter);
+
             // Noise
-            let mut noise_mix = self.params.noise_level.value() / 100.0;
+            let mut noise_mix = self.value(&self.params.noise_level) / 100.0;
             noise_mix *= noise_mix;
             self.synth.noise_mix = noise_mix * 0.06;
 
@@ -812,10 +2643,42 @@ impl Plugin for RX11 {
             self.synth
                 .render(output, block_start, block_end, &self.params);
 
+            // Let the host know which voices are done so it can stop showing their modulation
+            for voice in &mut self.synth.voices {
+                if let Some((voice_id, channel, note)) = voice.stolen_voice_id.take() {
+                    context.send_event(NoteEvent::VoiceTerminated {
+                        timing: block_start as u32,
+                        voice_id: Some(voice_id),
+                        channel,
+                        note: note as u8,
+                    });
+                }
+
+                if !voice.envelope.is_active() && !voice.gated {
+                    if let Some(voice_id) = voice.voice_id.take() {
+                        context.send_event(NoteEvent::VoiceTerminated {
+                            timing: block_start as u32,
+                            voice_id: Some(voice_id),
+                            channel: voice.channel,
+                            note: voice.key as u8,
+                        });
+                    }
+                }
+            }
+
+            self.arp
+                .advance((block_end - block_start) as f64 * beats_per_sample);
+            self.seq
+                .advance((block_end - block_start) as f64 * beats_per_sample);
+
             block_start = block_end;
             block_end = (block_start + MAX_BLOCK_SIZE).min(num_samples);
         }
 
+        if param_changes_queued {
+            context.execute_gui(Task::ApplyParamChanges);
+        }
+
         ProcessStatus::Normal
     }
 
@@ -836,6 +2699,10 @@ impl ClapPlugin for RX11 {
         ClapFeature::Mono,
         ClapFeature::Utility,
     ];
+    const CLAP_POLY_MODULATION_CONFIG: Option<PolyModulationConfig> = Some(PolyModulationConfig {
+        max_voice_capacity: crate::synth::MAX_VOICES as u32,
+        supports_overlapping_voices: true,
+    });
 }
 
 impl Vst3Plugin for RX11 {
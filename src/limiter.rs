@@ -0,0 +1,136 @@
+// The limiter starts pulling the level down at -1 dBFS, and the soft clipper after it never lets
+// a sample past -0.3 dBFS
+const THRESHOLD: f32 = 0.891;
+const CEILING: f32 = 0.966;
+
+/// One pole high pass at 10 Hz that takes out the DC offset of the oscillators and filter
+pub struct DcBlocker {
+    coefficient: f32,
+    previous_input: [f32; 2],
+    previous_output: [f32; 2],
+}
+
+impl DcBlocker {
+    pub fn new() -> Self {
+        Self {
+            coefficient: 0.9986,
+            previous_input: [0.0; 2],
+            previous_output: [0.0; 2],
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.coefficient = 1.0 - std::f32::consts::TAU * 10.0 / sample_rate;
+    }
+
+    pub fn reset(&mut self) {
+        self.previous_input = [0.0; 2];
+        self.previous_output = [0.0; 2];
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let channels = self.previous_input.iter_mut().zip(&mut self.previous_output);
+        for ((previous_input, previous_output), input) in channels.zip([left, right]) {
+            *previous_output = input - *previous_input + self.coefficient * *previous_output;
+            *previous_input = input;
+        }
+
+        (self.previous_output[0], self.previous_output[1])
+    }
+}
+
+/// Peak limiter without lookahead. Both sides share the gain so the stereo image doesn't move,
+/// whatever gets past the 1 ms attack is caught by a soft clipper.
+pub struct Limiter {
+    pub enabled: bool,
+    attack: f32,
+    release: f32,
+    envelope: f32,
+}
+
+// Leaves everything below the threshold alone and bends the rest smoothly toward the ceiling
+fn soft_clip(sample: f32) -> f32 {
+    let level = sample.abs();
+    if level <= THRESHOLD {
+        return sample;
+    }
+
+    let knee = CEILING - THRESHOLD;
+    sample.signum() * (THRESHOLD + knee * ((level - THRESHOLD) / knee).tanh())
+}
+
+impl Limiter {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            attack: 0.02,
+            release: 0.0002,
+            envelope: 0.0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.attack = 1.0 - (-1.0 / (0.001 * sample_rate)).exp();
+        self.release = 1.0 - (-1.0 / (0.1 * sample_rate)).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        if !self.enabled {
+            self.envelope = 0.0;
+            return (left, right);
+        }
+
+        let peak = left.abs().max(right.abs());
+        let coefficient = if peak > self.envelope { self.attack } else { self.release };
+        self.envelope += coefficient * (peak - self.envelope);
+
+        let gain = if self.envelope > THRESHOLD {
+            THRESHOLD / self.envelope
+        } else {
+            1.0
+        };
+
+        (soft_clip(left * gain), soft_clip(right * gain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dc_blocker_removes_the_offset() {
+        let mut dc_blocker = DcBlocker::new();
+        dc_blocker.set_sample_rate(48_000.0);
+
+        let mut output = (0.0, 0.0);
+        for _ in 0..48_000 {
+            output = dc_blocker.process(0.5, -0.5);
+        }
+
+        assert!(output.0.abs() < 0.001);
+        assert!(output.1.abs() < 0.001);
+    }
+
+    #[test]
+    fn limiter_keeps_the_peaks_under_the_ceiling() {
+        let mut limiter = Limiter::new();
+        limiter.enabled = true;
+        limiter.set_sample_rate(48_000.0);
+
+        for i in 0..48_000 {
+            let sample = 4.0 * (i as f32 * 0.05).sin();
+            let (left, right) = limiter.process(sample, -sample);
+            assert!(left.abs() <= CEILING);
+            assert!(right.abs() <= CEILING);
+        }
+
+        // Quiet signals pass through untouched once the gain has recovered
+        limiter.reset();
+        assert_eq!(limiter.process(0.5, -0.25), (0.5, -0.25));
+    }
+}
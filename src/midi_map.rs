@@ -0,0 +1,81 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Binds a MIDI CC to a parameter. The range is in normalized 0..1 parameter values.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiMapping {
+    pub cc: u8,
+    pub param_id: String,
+    pub min: f32,
+    pub max: f32,
+    pub invert: bool,
+}
+
+impl MidiMapping {
+    pub fn new(cc: u8, param_id: String) -> Self {
+        Self {
+            cc,
+            param_id,
+            min: 0.0,
+            max: 1.0,
+            invert: false,
+        }
+    }
+
+    /// The normalized parameter value for a CC value in 0..1
+    pub fn normalized_value(&self, cc_value: f32) -> f32 {
+        let value = if self.invert { 1.0 - cc_value } else { cc_value };
+        (self.min + (self.max - self.min) * value).clamp(0.0, 1.0)
+    }
+}
+
+/// Shared between the editor, which arms MIDI learn for a parameter, and the audio thread, which
+/// reports the first CC that comes in while it is armed.
+pub struct MidiLearn {
+    /// ID of the parameter waiting for a CC. Only the editor touches this.
+    pub target: Mutex<Option<String>>,
+    /// -2 when not learning, -1 when waiting for a CC, otherwise the CC that was received
+    learned_cc: AtomicI32,
+}
+
+impl MidiLearn {
+    pub fn new() -> Self {
+        Self {
+            target: Mutex::new(None),
+            learned_cc: AtomicI32::new(-2),
+        }
+    }
+
+    pub fn arm(&self, param_id: &str) {
+        *self.target.lock() = Some(param_id.to_string());
+        self.learned_cc.store(-1, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        *self.target.lock() = None;
+        self.learned_cc.store(-2, Ordering::Relaxed);
+    }
+
+    pub fn is_learning(&self, param_id: &str) -> bool {
+        self.target.lock().as_deref() == Some(param_id)
+    }
+
+    /// Called from the audio thread for every CC
+    pub fn cc_received(&self, cc: u8) {
+        let _ = self
+            .learned_cc
+            .compare_exchange(-1, cc as i32, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// The parameter ID and the CC it should be bound to, once one has been received
+    pub fn take_learned(&self) -> Option<(String, u8)> {
+        let cc = self.learned_cc.load(Ordering::Relaxed);
+        if cc < 0 {
+            return None;
+        }
+
+        self.learned_cc.store(-2, Ordering::Relaxed);
+        self.target.lock().take().map(|param_id| (param_id, cc as u8))
+    }
+}
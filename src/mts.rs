@@ -0,0 +1,293 @@
+use nih_plug::prelude::SysExMessage;
+
+/// A single note tuning change for all 128 notes is the largest message, 7 header bytes, 4 bytes
+/// for each note and the end byte
+pub const MAX_SYSEX_SIZE: usize = 520;
+
+const SYSEX_START: u8 = 0xF0;
+const SYSEX_END: u8 = 0xF7;
+const NON_REALTIME: u8 = 0x7E;
+const REALTIME: u8 = 0x7F;
+const MIDI_TUNING: u8 = 0x08;
+
+const BULK_DUMP: u8 = 0x01;
+const NOTE_CHANGE: u8 = 0x02;
+const NOTE_CHANGE_BANK: u8 = 0x07;
+const SCALE_OCTAVE_1_BYTE: u8 = 0x08;
+const SCALE_OCTAVE_2_BYTE: u8 = 0x09;
+
+/// Pitches are kept in 1/512ths of a semitone, about 0.2 cents, so 128 of them fit in 16 bits each
+const PITCH_STEPS: f32 = 512.0;
+/// Marks the notes a message doesn't retune
+const NO_CHANGE: u16 = u16::MAX;
+
+/// A MIDI Tuning Standard message, decoded into the pitch of every note it retunes. All kinds of
+/// messages take the same space this way, and a `NoteEvent` is as large as its biggest message,
+/// so this keeps every event in the host's event queues from taking a kilobyte.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MtsMessage {
+    /// Real-time changes also retune the notes that are playing
+    pub realtime: bool,
+    pitches: [u16; 128],
+}
+
+impl MtsMessage {
+    pub fn new(realtime: bool) -> Self {
+        Self {
+            realtime,
+            pitches: [NO_CHANGE; 128],
+        }
+    }
+
+    /// Sets the pitch of a note in MIDI note numbers, like `Synth::tuning_table`
+    pub fn retune(&mut self, note: usize, pitch: f32) {
+        self.pitches[note] = (pitch.clamp(0.0, 127.99) * PITCH_STEPS).round() as u16;
+    }
+
+    /// The notes the message retunes and their pitches
+    pub fn changes(&self) -> impl Iterator<Item = (usize, f32)> + '_ {
+        self.pitches
+            .iter()
+            .enumerate()
+            .filter(|(_, pitch)| **pitch != NO_CHANGE)
+            .map(|(note, pitch)| (note, *pitch as f32 / PITCH_STEPS))
+    }
+
+    // Three bytes, the semitone and a 14 bit fraction of a semitone. 7F 7F 7F means no change.
+    fn set_encoded_pitch(&mut self, note: usize, data: &[u8]) {
+        let [semitone, msb, lsb] = *data else {
+            return;
+        };
+        if [semitone, msb, lsb] == [0x7F; 3] {
+            return;
+        }
+
+        let fraction = ((msb as u32 & 0x7F) << 7) | (lsb as u32 & 0x7F);
+        let pitch = ((semitone as u32 & 0x7F) << 9) + ((fraction + 16) >> 5);
+        self.pitches[note] = pitch.min(NO_CHANGE as u32 - 1) as u16;
+    }
+}
+
+fn encode_pitch(pitch: u16) -> [u8; 3] {
+    let fraction = (pitch as u32 & 0x1FF) << 5;
+    [(pitch >> 9) as u8, (fraction >> 7) as u8, (fraction & 0x7F) as u8]
+}
+
+fn decode_note_changes(realtime: bool, data: &[u8]) -> Option<MtsMessage> {
+    let (&count, data) = data.split_first()?;
+    let mut message = MtsMessage::new(realtime);
+
+    for change in data.chunks_exact(4).take(count as usize) {
+        message.set_encoded_pitch((change[0] & 0x7F) as usize, &change[1..]);
+    }
+
+    Some(message)
+}
+
+// Scale/octave messages give an offset in cents for each pitch class of 12-TET, the same in every
+// octave
+fn scale_octave(realtime: bool, offset: impl Fn(usize) -> f32) -> MtsMessage {
+    let mut message = MtsMessage::new(realtime);
+
+    for note in 0..128 {
+        message.retune(note, note as f32 + offset(note % 12) / 100.0);
+    }
+
+    message
+}
+
+impl SysExMessage for MtsMessage {
+    type Buffer = [u8; MAX_SYSEX_SIZE];
+
+    fn from_buffer(buffer: &[u8]) -> Option<Self> {
+        // Start, real-time or not, device ID, MIDI tuning, then the message and the end byte
+        let (&[SYSEX_START, kind, _device, MIDI_TUNING, message], rest) = buffer.split_first_chunk::<5>()?
+        else {
+            return None;
+        };
+        let (&SYSEX_END, data) = rest.split_last()? else {
+            return None;
+        };
+        let realtime = match kind {
+            NON_REALTIME => false,
+            REALTIME => true,
+            _ => return None,
+        };
+
+        match message {
+            // Tuning program, name, 128 pitches and a checksum
+            BULK_DUMP if data.len() >= 1 + 16 + 3 * 128 => {
+                let mut message = MtsMessage::new(false);
+                for (note, pitch) in data[17..17 + 3 * 128].chunks_exact(3).enumerate() {
+                    message.set_encoded_pitch(note, pitch);
+                }
+                Some(message)
+            }
+            // Tuning program, then the changes
+            NOTE_CHANGE => decode_note_changes(realtime, data.get(1..)?),
+            // Tuning bank and program, then the changes
+            NOTE_CHANGE_BANK => decode_note_changes(realtime, data.get(2..)?),
+            // Three bytes of channel mask, then one value per pitch class. -64..63 cents with one
+            // byte, or -100..100 cents with a 14 bit value.
+            SCALE_OCTAVE_1_BYTE if data.len() >= 3 + 12 => Some(scale_octave(realtime, |idx| {
+                data[3 + idx] as f32 - 64.0
+            })),
+            SCALE_OCTAVE_2_BYTE if data.len() >= 3 + 24 => Some(scale_octave(realtime, |idx| {
+                let value = ((data[3 + 2 * idx] as u32) << 7) | data[4 + 2 * idx] as u32;
+                (value as f32 - 8192.0) * 100.0 / 8192.0
+            })),
+            _ => None,
+        }
+    }
+
+    fn to_buffer(self) -> (Self::Buffer, usize) {
+        let mut buffer = [0; MAX_SYSEX_SIZE];
+        // Only a bulk dump can retune all 128 notes at once, but it isn't real-time
+        let bulk_dump = !self.realtime && !self.pitches.contains(&NO_CHANGE);
+
+        buffer[0] = SYSEX_START;
+        buffer[1] = if self.realtime { REALTIME } else { NON_REALTIME };
+        buffer[2] = 0x7F; // All devices
+        buffer[3] = MIDI_TUNING;
+
+        let len = if bulk_dump {
+            buffer[4] = BULK_DUMP;
+            // Program 0 and a blank name
+            buffer[5] = 0;
+            buffer[6..22].fill(b' ');
+            let mut len = 22;
+
+            for pitch in self.pitches {
+                buffer[len..len + 3].copy_from_slice(&encode_pitch(pitch));
+                len += 3;
+            }
+
+            // XOR of everything after the start byte, up to the checksum
+            buffer[len] = buffer[1..len].iter().fold(0, |sum, byte| sum ^ byte) & 0x7F;
+            len + 1
+        } else {
+            buffer[4] = NOTE_CHANGE;
+            buffer[5] = 0;
+            let mut len = 7;
+            let mut count = 0;
+
+            // The count is a single data byte, so the last of 128 changes doesn't fit
+            for (note, pitch) in self.pitches.iter().enumerate() {
+                if *pitch != NO_CHANGE && count < 127 {
+                    buffer[len] = note as u8;
+                    buffer[len + 1..len + 4].copy_from_slice(&encode_pitch(*pitch));
+                    len += 4;
+                    count += 1;
+                }
+            }
+
+            buffer[6] = count;
+            len
+        };
+
+        buffer[len] = SYSEX_END;
+        (buffer, len + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: MtsMessage) -> Option<MtsMessage> {
+        let (buffer, len) = message.to_buffer();
+        MtsMessage::from_buffer(&buffer[..len])
+    }
+
+    #[test]
+    fn bulk_dumps_round_trip() {
+        let mut message = MtsMessage::new(false);
+        for note in 0..128 {
+            message.retune(note, note as f32 * 1.5 / 2.0 + 0.25);
+        }
+
+        let (buffer, len) = message.to_buffer();
+        assert_eq!(buffer[4], BULK_DUMP);
+        assert_eq!(len, 5 + 1 + 16 + 3 * 128 + 2);
+        assert_eq!(MtsMessage::from_buffer(&buffer[..len]), Some(message));
+    }
+
+    #[test]
+    fn note_changes_round_trip() {
+        let mut message = MtsMessage::new(true);
+        message.retune(60, 60.5);
+        message.retune(0, 0.0);
+        message.retune(127, 127.99);
+
+        assert_eq!(round_trip(message), Some(message));
+        assert_eq!(
+            message.changes().collect::<Vec<_>>(),
+            [(0, 0.0), (60, 60.5), (127, (127.99 * 512.0_f32).round() / 512.0)]
+        );
+
+        let message = MtsMessage::new(false);
+        assert_eq!(round_trip(message), Some(message));
+    }
+
+    #[test]
+    fn decodes_pitches() {
+        // Note 69 to 69 and a quarter semitone, note 70 left as it is
+        let buffer = [
+            0xF0, 0x7F, 0x7F, 0x08, 0x02, 0x00, 0x02, 69, 69, 0x20, 0x00, 70, 0x7F, 0x7F, 0x7F,
+            0xF7,
+        ];
+        let message = MtsMessage::from_buffer(&buffer).unwrap();
+
+        assert!(message.realtime);
+        assert_eq!(message.changes().collect::<Vec<_>>(), [(69, 69.25)]);
+    }
+
+    #[test]
+    fn decodes_scale_octave_offsets() {
+        // One byte per pitch class, C is 10 cents flat and the rest are left in tune
+        let mut buffer = vec![0xF0, 0x7E, 0x7F, 0x08, 0x08, 0x03, 0x7F, 0x7F, 54];
+        buffer.extend([64; 11]);
+        buffer.push(0xF7);
+        let message = MtsMessage::from_buffer(&buffer).unwrap();
+
+        let pitches: Vec<_> = message.changes().collect();
+        assert_eq!(pitches.len(), 128);
+        assert!((pitches[60].1 - 59.9).abs() < 0.002);
+        assert_eq!(pitches[61].1, 61.0);
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let valid = [0xF0, 0x7F, 0x7F, 0x08, 0x02, 0x00, 0x01, 60, 60, 0x00, 0x00, 0xF7];
+        assert!(MtsMessage::from_buffer(&valid).is_some());
+
+        // Missing the end byte
+        assert_eq!(MtsMessage::from_buffer(&valid[..valid.len() - 1]), None);
+        // Not a tuning message
+        let mut other = valid;
+        other[3] = 0x09;
+        assert_eq!(MtsMessage::from_buffer(&other), None);
+        // Neither real-time nor non-real-time
+        let mut other = valid;
+        other[1] = 0x43;
+        assert_eq!(MtsMessage::from_buffer(&other), None);
+        // Unknown message
+        let mut other = valid;
+        other[4] = 0x05;
+        assert_eq!(MtsMessage::from_buffer(&other), None);
+        // Too short for a bulk dump
+        let short_dump = [0xF0, 0x7E, 0x7F, 0x08, 0x01, 0x00, 0xF7];
+        assert_eq!(MtsMessage::from_buffer(&short_dump), None);
+        assert_eq!(MtsMessage::from_buffer(&[]), None);
+        assert_eq!(MtsMessage::from_buffer(&[0xF0, 0xF7]), None);
+    }
+
+    #[test]
+    fn truncated_note_changes_keep_the_complete_ones() {
+        // Says two changes but the second one is cut off
+        let buffer = [0xF0, 0x7F, 0x7F, 0x08, 0x02, 0x00, 0x02, 60, 61, 0x00, 0x00, 62, 62, 0xF7];
+        let message = MtsMessage::from_buffer(&buffer).unwrap();
+
+        assert_eq!(message.changes().collect::<Vec<_>>(), [(60, 61.0)]);
+    }
+}
@@ -11,7 +11,11 @@ impl NoiseGenerator {
     }
 
     pub fn next_value(&mut self) -> f32 {
-        self.noise_seed = self.noise_seed * 196314165 + 907633515;
+        // Relies on the overflow wrapping around, which debug builds would otherwise panic on
+        self.noise_seed = self
+            .noise_seed
+            .wrapping_mul(196314165)
+            .wrapping_add(907633515);
         let temp = ((self.noise_seed >> 7) as i32) - 16777216;
         temp as f32 / 16777216.0f32
     }
@@ -0,0 +1,133 @@
+/// Keeps track of the keys held down in mono mode, in the order they were pressed.
+/// A fixed size array is used so pushing notes never allocates on the audio thread.
+pub struct NoteStack {
+    notes: [(i32, f32); 128],
+    len: usize,
+}
+
+impl NoteStack {
+    pub fn new() -> Self {
+        Self {
+            notes: [(0, 0.0); 128],
+            len: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The held notes and their velocities, oldest first
+    pub fn notes(&self) -> &[(i32, f32)] {
+        &self.notes[..self.len]
+    }
+
+    pub fn push(&mut self, note: i32, velocity: f32) {
+        // A note can only be held once, pressing it again moves it to the top
+        self.remove(note);
+
+        if self.len == self.notes.len() {
+            self.notes.copy_within(1.., 0);
+            self.len -= 1;
+        }
+
+        self.notes[self.len] = (note, velocity);
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, note: i32) {
+        if let Some(idx) = self.notes[..self.len].iter().position(|(n, _)| *n == note) {
+            self.notes.copy_within((idx + 1)..self.len, idx);
+            self.len -= 1;
+        }
+    }
+
+    /// The note and velocity that should sound for the given priority
+    pub fn priority_note(&self, priority: &crate::NotePriority) -> Option<(i32, f32)> {
+        let held = self.notes[..self.len].iter();
+
+        match priority {
+            crate::NotePriority::Last => held.last(),
+            crate::NotePriority::Lowest => held.min_by_key(|(note, _)| *note),
+            crate::NotePriority::Highest => held.max_by_key(|(note, _)| *note),
+        }
+        .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NotePriority;
+
+    fn keys(stack: &NoteStack) -> Vec<i32> {
+        stack.notes().iter().map(|(note, _)| *note).collect()
+    }
+
+    #[test]
+    fn push_keeps_the_press_order() {
+        let mut stack = NoteStack::new();
+        stack.push(64, 1.0);
+        stack.push(60, 2.0);
+        stack.push(67, 3.0);
+        assert_eq!(keys(&stack), [64, 60, 67]);
+
+        // Pressing a held key again moves it to the top with the new velocity
+        stack.push(64, 4.0);
+        assert_eq!(stack.notes(), [(60, 2.0), (67, 3.0), (64, 4.0)]);
+    }
+
+    #[test]
+    fn remove_closes_the_gap() {
+        let mut stack = NoteStack::new();
+        stack.push(60, 1.0);
+        stack.push(62, 1.0);
+        stack.push(64, 1.0);
+
+        stack.remove(62);
+        assert_eq!(keys(&stack), [60, 64]);
+
+        // Keys that aren't held are ignored
+        stack.remove(70);
+        assert_eq!(keys(&stack), [60, 64]);
+
+        stack.remove(60);
+        stack.remove(64);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn a_full_stack_drops_the_oldest_key() {
+        let mut stack = NoteStack::new();
+        for note in 0..129 {
+            stack.push(note, 1.0);
+        }
+
+        assert_eq!(stack.notes().len(), 128);
+        assert_eq!(stack.notes()[0].0, 1);
+        assert_eq!(stack.notes()[127].0, 128);
+    }
+
+    #[test]
+    fn priority_picks_the_note() {
+        let mut stack = NoteStack::new();
+        assert_eq!(stack.priority_note(&NotePriority::Last), None);
+
+        stack.push(64, 1.0);
+        stack.push(60, 2.0);
+        stack.push(67, 3.0);
+        stack.push(62, 4.0);
+
+        assert_eq!(stack.priority_note(&NotePriority::Last), Some((62, 4.0)));
+        assert_eq!(stack.priority_note(&NotePriority::Lowest), Some((60, 2.0)));
+        assert_eq!(stack.priority_note(&NotePriority::Highest), Some((67, 3.0)));
+
+        // The previous key takes over when the last one is let go
+        stack.remove(62);
+        assert_eq!(stack.priority_note(&NotePriority::Last), Some((67, 3.0)));
+    }
+}
@@ -0,0 +1,275 @@
+use nih_plug::prelude::*;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::presets::PresetTarget;
+
+/// nih_plug has no way to change a parameter from the audio thread, so mapped CCs and program
+/// changes are queued here and applied on the GUI thread through the host's `GuiContext`, the same
+/// way the editor changes them. Every parameter has a slot, so only its latest value is kept.
+///
+/// The host only hands out the context when it opens the editor, so a change can't always be
+/// applied right away. Until it is, `value` returns it in place of the parameter's own value and
+/// the plugin sounds the same either way. A change the host hasn't applied is dropped once the
+/// host changes the parameter itself, like automation taking over.
+pub struct ParamChanges {
+    params: Vec<ParamPtr>,
+    /// Slot indices by parameter ID and by pointer, built once so lookups don't search
+    ids: HashMap<String, usize>,
+    ptrs: HashMap<ParamPtr, usize>,
+    /// The normalized value waiting in each slot
+    values: Vec<AtomicF32>,
+    /// The parameter's own normalized value when the change was queued
+    base_values: Vec<AtomicF32>,
+    pending: Vec<AtomicBool>,
+    num_pending: AtomicUsize,
+    gui_context: Mutex<Option<Arc<dyn GuiContext>>>,
+}
+
+impl ParamChanges {
+    pub fn new(params: &impl Params) -> Self {
+        let params: Vec<(String, ParamPtr)> = params
+            .param_map()
+            .into_iter()
+            .map(|(id, param, _)| (id, param))
+            .collect();
+
+        Self {
+            ids: params
+                .iter()
+                .enumerate()
+                .map(|(idx, (id, _))| (id.clone(), idx))
+                .collect(),
+            ptrs: params
+                .iter()
+                .enumerate()
+                .map(|(idx, (_, param))| (*param, idx))
+                .collect(),
+            values: params.iter().map(|_| AtomicF32::new(0.0)).collect(),
+            base_values: params.iter().map(|_| AtomicF32::new(0.0)).collect(),
+            pending: params.iter().map(|_| AtomicBool::new(false)).collect(),
+            params: params.into_iter().map(|(_, param)| param).collect(),
+            num_pending: AtomicUsize::new(0),
+            gui_context: Mutex::new(None),
+        }
+    }
+
+    fn set(&self, idx: usize, normalized: f32) {
+        // SAFETY: The pointers come from the plugin's parameters, which outlive this
+        let base_value = unsafe { self.params[idx].unmodulated_normalized_value() };
+        self.values[idx].store(normalized, Ordering::Relaxed);
+        self.base_values[idx].store(base_value, Ordering::Relaxed);
+
+        if !self.pending[idx].swap(true, Ordering::Release) {
+            self.num_pending.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Queues a normalized value for the parameter with the ID, unknown IDs are ignored
+    pub fn set_normalized(&self, param_id: &str, normalized: f32) {
+        if let Some(&idx) = self.ids.get(param_id) {
+            self.set(idx, normalized);
+        }
+    }
+
+    /// The queued value the host hasn't applied to the parameter yet, if there is one
+    pub fn pending<P: Param>(&self, param: &P) -> Option<P::Plain> {
+        // Saves looking the parameter up when nothing is queued, which is almost always
+        if self.num_pending.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+
+        let &idx = self.ptrs.get(&param.as_ptr())?;
+        if !self.pending[idx].load(Ordering::Acquire) {
+            return None;
+        }
+
+        // The host has changed the parameter since, so its value is newer
+        if param.unmodulated_normalized_value() != self.base_values[idx].load(Ordering::Relaxed) {
+            if self.pending[idx].swap(false, Ordering::Relaxed) {
+                self.num_pending.fetch_sub(1, Ordering::Relaxed);
+            }
+            return None;
+        }
+
+        Some(param.preview_plain(self.values[idx].load(Ordering::Relaxed)))
+    }
+
+    /// The parameter's value including the changes the host hasn't applied yet. The audio thread
+    /// reads the parameters through this.
+    pub fn value<P: Param>(&self, param: &P) -> P::Plain {
+        self.pending(param)
+            .unwrap_or_else(|| param.modulated_plain_value())
+    }
+
+    /// Called from the editor with the context the host gave it, which stays valid after the
+    /// editor is closed
+    pub fn set_gui_context(&self, context: Arc<dyn GuiContext>) {
+        *self.gui_context.lock() = Some(context);
+    }
+
+    /// Hands the queued values to the host. Only call this from the GUI thread.
+    pub fn apply(&self) {
+        let Some(context) = self.gui_context.lock().clone() else {
+            return;
+        };
+
+        for ((param, value), pending) in self.params.iter().zip(&self.values).zip(&self.pending) {
+            if !pending.swap(false, Ordering::Acquire) {
+                continue;
+            }
+
+            // SAFETY: The pointers come from the plugin's parameters, which outlive the plugin's
+            // tasks and editor
+            unsafe {
+                context.raw_begin_set_parameter(*param);
+                context.raw_set_parameter_normalized(*param, value.load(Ordering::Relaxed));
+                context.raw_end_set_parameter(*param);
+            }
+            self.num_pending.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl PresetTarget for &ParamChanges {
+    fn set_param<P: Param>(&mut self, param: &P, value: P::Plain) {
+        if let Some(&idx) = self.ptrs.get(&param.as_ptr()) {
+            self.set(idx, param.preview_normalized(value));
+        }
+    }
+}
+
+/// Wraps the editor to hold on to the `GuiContext` the host gives it when it is opened
+pub struct ParamChangesEditor {
+    pub editor: Box<dyn Editor>,
+    pub param_changes: Arc<ParamChanges>,
+}
+
+impl Editor for ParamChangesEditor {
+    fn spawn(&self, parent: ParentWindowHandle, context: Arc<dyn GuiContext>) -> Box<dyn Any + Send> {
+        self.param_changes.set_gui_context(context.clone());
+        self.param_changes.apply();
+
+        self.editor.spawn(parent, context)
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.editor.size()
+    }
+
+    fn set_scale_factor(&self, factor: f32) -> bool {
+        self.editor.set_scale_factor(factor)
+    }
+
+    fn param_value_changed(&self, id: &str, normalized_value: f32) {
+        self.editor.param_value_changed(id, normalized_value)
+    }
+
+    fn param_modulation_changed(&self, id: &str, modulation_offset: f32) {
+        self.editor.param_modulation_changed(id, modulation_offset)
+    }
+
+    fn param_values_changed(&self) {
+        self.editor.param_values_changed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi_map::MidiMapping;
+    use crate::RX11Params;
+
+    // Records the values the host is asked to set
+    #[derive(Default)]
+    struct TestContext {
+        values: Mutex<Vec<(ParamPtr, f32)>>,
+    }
+
+    impl GuiContext for TestContext {
+        fn plugin_api(&self) -> PluginApi {
+            PluginApi::Clap
+        }
+
+        fn request_resize(&self) -> bool {
+            false
+        }
+
+        unsafe fn raw_begin_set_parameter(&self, _param: ParamPtr) {}
+
+        unsafe fn raw_set_parameter_normalized(&self, param: ParamPtr, normalized: f32) {
+            self.values.lock().push((param, normalized));
+        }
+
+        unsafe fn raw_end_set_parameter(&self, _param: ParamPtr) {}
+
+        fn get_state(&self) -> PluginState {
+            unimplemented!()
+        }
+
+        fn set_state(&self, _state: PluginState) {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn mapped_ccs_work_without_the_editor() {
+        let params = RX11Params::default();
+        let changes = ParamChanges::new(&params);
+        let mapping = MidiMapping::new(7, "output".to_string());
+
+        changes.set_normalized(&mapping.param_id, mapping.normalized_value(0.25));
+        (&changes).set_param(&params.polyphony, 4);
+        // Without a GUI context there is nothing to apply them with
+        changes.apply();
+
+        let output_level = params.output_level.preview_plain(0.25);
+        assert_eq!(changes.pending(&params.output_level), Some(output_level));
+        assert_eq!(changes.value(&params.output_level), output_level);
+        assert_eq!(changes.value(&params.polyphony), 4);
+        assert_eq!(changes.pending(&params.osc_mix), None);
+        assert_eq!(changes.value(&params.osc_mix), params.osc_mix.value());
+    }
+
+    #[test]
+    fn changes_wait_for_the_gui_context() {
+        let params = RX11Params::default();
+        let changes = ParamChanges::new(&params);
+
+        changes.set_normalized("output", 0.25);
+        changes.apply();
+
+        let context = Arc::new(TestContext::default());
+        changes.set_gui_context(context.clone());
+        changes.apply();
+
+        assert_eq!(*context.values.lock(), vec![(params.output_level.as_ptr(), 0.25)]);
+        // From now on the parameter's own value is the applied one
+        assert_eq!(changes.pending(&params.output_level), None);
+    }
+
+    #[test]
+    fn only_the_latest_value_is_applied() {
+        let params = RX11Params::default();
+        let changes = ParamChanges::new(&params);
+        let context = Arc::new(TestContext::default());
+        changes.set_gui_context(context.clone());
+
+        changes.set_normalized("output", 0.25);
+        changes.set_normalized("output", 0.75);
+        changes.set_normalized("not_a_param", 0.5);
+        (&changes).set_param(&params.polyphony, 4);
+        changes.apply();
+
+        let values = context.values.lock();
+        assert_eq!(values.len(), 2);
+        assert!(values.contains(&(params.output_level.as_ptr(), 0.75)));
+        assert!(values.contains(&(
+            params.polyphony.as_ptr(),
+            params.polyphony.preview_normalized(4)
+        )));
+    }
+}
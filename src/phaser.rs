@@ -0,0 +1,73 @@
+const NUM_STAGES: usize = 6;
+
+// The lowest frequency of the sweep in Hz, the depth sweeps up to five octaves above it
+const MIN_FREQ: f32 = 200.0;
+
+/// Six stage stereo phaser, the right side's LFO is a quarter cycle ahead of the left
+pub struct Phaser {
+    pub sample_rate: f32,
+    /// LFO rate in Hz
+    pub rate: f32,
+    /// 0..1
+    pub depth: f32,
+    /// 0..1, feeding the output back in makes the notches sharper
+    pub feedback: f32,
+    /// 0..1, the notches are deepest at 0.5
+    pub mix: f32,
+    lfo_phase: f32,
+    states: [[f32; NUM_STAGES]; 2],
+    last_output: [f32; 2],
+}
+
+impl Phaser {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            rate: 0.5,
+            depth: 0.7,
+            feedback: 0.5,
+            mix: 0.5,
+            lfo_phase: 0.0,
+            states: [[0.0; NUM_STAGES]; 2],
+            last_output: [0.0; 2],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.lfo_phase = 0.0;
+        self.states = [[0.0; NUM_STAGES]; 2];
+        self.last_output = [0.0; 2];
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        self.lfo_phase += self.rate / self.sample_rate;
+        if self.lfo_phase >= 1.0 {
+            self.lfo_phase -= 1.0;
+        }
+
+        let input = [left, right];
+        let mut output = [0.0; 2];
+
+        for channel in 0..2 {
+            let phase = self.lfo_phase + 0.25 * channel as f32;
+            let lfo = 0.5 + 0.5 * (std::f32::consts::TAU * phase).sin();
+            let freq = (MIN_FREQ * (5.0 * self.depth * lfo).exp2()).min(0.45 * self.sample_rate);
+
+            // First order all pass coefficient for the frequency
+            let t = (std::f32::consts::PI * freq / self.sample_rate).tan();
+            let a = (t - 1.0) / (t + 1.0);
+
+            let mut sample = input[channel] + self.feedback * 0.9 * self.last_output[channel];
+            for state in &mut self.states[channel] {
+                let all_pass = a * sample + *state;
+                *state = sample - a * all_pass;
+                sample = all_pass;
+            }
+
+            self.last_output[channel] = sample;
+            output[channel] = (1.0 - self.mix) * input[channel] + self.mix * sample;
+        }
+
+        (output[0], output[1])
+    }
+}
@@ -1,5 +1,12 @@
+use nih_plug::prelude::Param;
 use std::collections::HashMap;
 
+/// Where a preset's values end up, the editor sets the parameters and MIDI program changes queue
+/// them in `ParamChanges` on the audio thread.
+pub trait PresetTarget {
+    fn set_param<P: Param>(&mut self, param: &P, value: P::Plain);
+}
+
 #[derive(Clone)]
 pub struct Preset {
     pub name: String,
@@ -0,0 +1,192 @@
+/// The longest pre-delay in milliseconds
+pub const MAX_PRE_DELAY_MS: f32 = 200.0;
+
+const NUM_LINES: usize = 8;
+
+// Delay line lengths in milliseconds at the smallest size, spread out so their echoes don't line
+// up. The largest size makes them eight times as long.
+const LINE_MS: [f32; NUM_LINES] = [7.4, 9.3, 10.3, 10.9, 13.3, 15.0, 16.9, 18.3];
+const MAX_SIZE_SCALE: f32 = 8.0;
+
+// A delay line read with linear interpolation, the delay can be fractional and change smoothly
+struct DelayLine {
+    buffer: Vec<f32>,
+    write_idx: usize,
+}
+
+impl DelayLine {
+    fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            write_idx: 0,
+        }
+    }
+
+    fn allocate(&mut self, len: usize) {
+        self.buffer = vec![0.0; len.max(2)];
+        self.write_idx = 0;
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.write_idx = 0;
+    }
+
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(1.0, (len - 2) as f32);
+        let whole = delay as usize;
+        let fraction = delay - whole as f32;
+
+        let idx = (self.write_idx + len - whole) % len;
+        let previous_idx = (idx + len - 1) % len;
+        self.buffer[idx] * (1.0 - fraction) + self.buffer[previous_idx] * fraction
+    }
+
+    fn write(&mut self, sample: f32) {
+        self.buffer[self.write_idx] = sample;
+        self.write_idx = (self.write_idx + 1) % self.buffer.len();
+    }
+}
+
+/// Stereo feedback delay network reverb. Eight delay lines feed back into each other through a
+/// Hadamard matrix, each with a low pass filter for the damping.
+pub struct Reverb {
+    pub sample_rate: f32,
+    /// 0..1, scales the delay line lengths
+    pub size: f32,
+    /// Time in seconds for the tail to fall by 60 dB
+    pub decay: f32,
+    /// 0..1, how much faster the highs die out
+    pub damping: f32,
+    /// Pre-delay in milliseconds
+    pub pre_delay: f32,
+    /// 0..1, dry and wet are both at full level at 0.5
+    pub mix: f32,
+    pre_delay_line: [DelayLine; 2],
+    lines: [DelayLine; NUM_LINES],
+    damping_state: [f32; NUM_LINES],
+    // Smoothed so moving the controls doesn't click
+    size_scale: f32,
+    pre_delay_samples: f32,
+    wet: f32,
+}
+
+impl Reverb {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            size: 0.5,
+            decay: 2.0,
+            damping: 0.5,
+            pre_delay: 10.0,
+            mix: 0.25,
+            pre_delay_line: std::array::from_fn(|_| DelayLine::new()),
+            lines: std::array::from_fn(|_| DelayLine::new()),
+            damping_state: [0.0; NUM_LINES],
+            size_scale: 1.0,
+            pre_delay_samples: 0.0,
+            wet: 0.0,
+        }
+    }
+
+    /// Allocates the delay lines for the sample rate, so processing never has to
+    pub fn initialize(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let samples_per_ms = 0.001 * sample_rate;
+
+        for line in &mut self.pre_delay_line {
+            line.allocate((MAX_PRE_DELAY_MS * samples_per_ms) as usize + 2);
+        }
+
+        for (line, ms) in self.lines.iter_mut().zip(LINE_MS) {
+            line.allocate((ms * MAX_SIZE_SCALE * samples_per_ms) as usize + 2);
+        }
+
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        for line in self.pre_delay_line.iter_mut().chain(self.lines.iter_mut()) {
+            line.clear();
+        }
+
+        self.damping_state = [0.0; NUM_LINES];
+        self.size_scale = self.target_size_scale();
+        self.pre_delay_samples = self.pre_delay * 0.001 * self.sample_rate;
+    }
+
+    fn target_size_scale(&self) -> f32 {
+        1.0 + (MAX_SIZE_SCALE - 1.0) * self.size.clamp(0.0, 1.0)
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        self.wet += 0.001 * (self.mix - self.wet);
+
+        // Before `initialize` there is nothing to process with
+        if self.lines[0].buffer.is_empty() {
+            return (left, right);
+        }
+
+        if self.wet < 0.0001 && self.mix == 0.0 {
+            // The tail stops, so it doesn't come back when the mix is turned up again
+            if self.damping_state.iter().any(|state| *state != 0.0) {
+                self.reset();
+            }
+            return (left, right);
+        }
+
+        self.size_scale += 0.0002 * (self.target_size_scale() - self.size_scale);
+        self.pre_delay_samples += 0.0002 * (self.pre_delay * 0.001 * self.sample_rate - self.pre_delay_samples);
+
+        let pre_delayed = [
+            self.pre_delay_line[0].read(self.pre_delay_samples),
+            self.pre_delay_line[1].read(self.pre_delay_samples),
+        ];
+        self.pre_delay_line[0].write(left);
+        self.pre_delay_line[1].write(right);
+
+        let samples_per_ms = 0.001 * self.sample_rate;
+        let damping = 0.05 + 0.9 * (1.0 - self.damping.clamp(0.0, 1.0));
+        let mut outputs = [0.0; NUM_LINES];
+
+        for (idx, line) in self.lines.iter().enumerate() {
+            let delay = LINE_MS[idx] * self.size_scale * samples_per_ms;
+
+            // Every line loses 60 dB over the decay time, longer lines lose more on each pass
+            let gain = 10.0_f32.powf(-3.0 * delay / (self.decay.max(0.1) * self.sample_rate));
+            let sample = line.read(delay) * gain;
+
+            self.damping_state[idx] += damping * (sample - self.damping_state[idx]);
+            outputs[idx] = self.damping_state[idx];
+        }
+
+        let wet_left = (outputs[0] + outputs[2] + outputs[4] + outputs[6]) * 0.5;
+        let wet_right = (outputs[1] + outputs[3] + outputs[5] + outputs[7]) * 0.5;
+
+        // Fast Walsh-Hadamard transform, mixes every line into every other one without changing
+        // the energy
+        let mut size = 1;
+        while size < NUM_LINES {
+            for start in (0..NUM_LINES).step_by(2 * size) {
+                for idx in start..start + size {
+                    let (a, b) = (outputs[idx], outputs[idx + size]);
+                    outputs[idx] = a + b;
+                    outputs[idx + size] = a - b;
+                }
+            }
+            size *= 2;
+        }
+
+        let normalize = 1.0 / (NUM_LINES as f32).sqrt();
+        for (idx, line) in self.lines.iter_mut().enumerate() {
+            // The left input feeds the even lines and the right input the odd ones
+            let input = pre_delayed[idx % 2] * if idx % 4 < 2 { 0.5 } else { -0.5 };
+            line.write(outputs[idx] * normalize + input);
+        }
+
+        let dry = (2.0 - 2.0 * self.wet).min(1.0);
+        let wet = (2.0 * self.wet).min(1.0);
+        (dry * left + wet * wet_left, dry * right + wet * wet_right)
+    }
+}
@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+
+use crate::note_stack::NoteStack;
+use crate::synth::Synth;
+
+pub const MAX_STEPS: usize = 32;
+
+/// Keys transpose the sequence by how far they are from middle C
+const TRANSPOSE_ROOT: i32 = 60;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    /// Off steps are rests
+    pub on: bool,
+    pub note: i32,
+    /// 0..127 like MIDI velocities
+    pub velocity: f32,
+    /// Portion of the step the note is held for, 0..1
+    pub gate: f32,
+    /// Holds the note into the next step and glides to it. Only mono mode slides without
+    /// retriggering the envelopes, with polyphony the next note gets a voice of its own.
+    pub slide: bool,
+    /// Boosts the filter envelope depth of the note
+    pub accent: bool,
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Self {
+            on: true,
+            note: 48,
+            velocity: 100.0,
+            gate: 0.5,
+            slide: false,
+            accent: false,
+        }
+    }
+}
+
+/// The steps edited in the editor and saved with the plugin state
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sequence {
+    pub steps: Vec<Step>,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Self {
+            steps: vec![Step::default(); MAX_STEPS],
+        }
+    }
+}
+
+/// A monophonic step sequencer in front of `Synth::note_on`/`note_off`. It runs while the host
+/// plays, or from the first step while a key is held when it doesn't, and the held key
+/// transposes it. Time is counted in quarter note beats like the arpeggiator.
+pub struct Sequencer {
+    pub enabled: bool,
+    pub length: usize,
+    /// Step length in beats
+    pub rate: f64,
+    /// Added to the filter envelope depth of accented notes
+    pub accent: f32,
+    /// A copy of the editor's steps, so playing never waits for its lock
+    steps: [Step; MAX_STEPS],
+    held: NoteStack,
+    sounding: Option<i32>,
+    position: f64,
+    next_step: i64,
+    note_off_at: Option<f64>,
+}
+
+impl Sequencer {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            length: 16,
+            rate: 0.25,
+            accent: 0.0,
+            steps: [Step::default(); MAX_STEPS],
+            held: NoteStack::new(),
+            sounding: None,
+            position: 0.0,
+            next_step: 0,
+            note_off_at: None,
+        }
+    }
+
+    pub fn reset(&mut self, synth: &mut Synth) {
+        self.release(synth);
+        self.held.clear();
+        self.next_step = self.step_after(self.position);
+    }
+
+    pub fn set_steps(&mut self, sequence: &Sequence) {
+        for (step, new_step) in self.steps.iter_mut().zip(&sequence.steps) {
+            *step = *new_step;
+        }
+    }
+
+    /// Follows the host's play position, jumps like loops move the grid along
+    pub fn sync(&mut self, position: f64) {
+        // Small differences are rounding, those shouldn't replay a step
+        let jumped = (position - self.position).abs() > 0.01;
+        self.position = position;
+
+        if jumped {
+            self.next_step = self.step_after(position);
+        }
+    }
+
+    pub fn set_rate(&mut self, rate: f64) {
+        if rate != self.rate {
+            self.rate = rate;
+            self.next_step = self.step_after(self.position);
+        }
+    }
+
+    pub fn note_on(&mut self, note: i32, velocity: f32, transport_playing: bool) {
+        // Without the host's transport the first key starts the sequence from the top
+        if self.held.is_empty() && !transport_playing {
+            self.position = 0.0;
+            self.next_step = 0;
+        }
+
+        self.held.push(note, velocity);
+    }
+
+    pub fn note_off(&mut self, note: i32) {
+        self.held.remove(note);
+    }
+
+    pub fn advance(&mut self, beats: f64) {
+        self.position += beats;
+    }
+
+    /// Plays the step or releases the note that is due at the current position
+    pub fn update(&mut self, synth: &mut Synth, transport_playing: bool) {
+        if self.note_off_at.is_some_and(|note_off| self.position >= note_off) {
+            self.release(synth);
+        }
+
+        if !transport_playing && self.held.is_empty() {
+            self.release(synth);
+            self.next_step = self.step_after(self.position);
+            return;
+        }
+
+        let step_start = self.next_step as f64 * self.rate;
+        if self.position >= step_start {
+            self.play_step(synth, step_start);
+            self.next_step += 1;
+        }
+    }
+
+    /// How many beats until the next note on or note off, `None` when it isn't running
+    pub fn beats_until_next_event(&self, transport_playing: bool) -> Option<f64> {
+        if !transport_playing && self.held.is_empty() && self.sounding.is_none() {
+            return None;
+        }
+
+        let mut next_event = self.next_step as f64 * self.rate;
+
+        if let Some(note_off) = self.note_off_at {
+            next_event = next_event.min(note_off);
+        }
+
+        Some((next_event - self.position).max(0.0))
+    }
+
+    pub fn release(&mut self, synth: &mut Synth) {
+        if let Some(note) = self.sounding.take() {
+            synth.note_off(note);
+        }
+
+        self.note_off_at = None;
+    }
+
+    fn step_after(&self, position: f64) -> i64 {
+        (position / self.rate).ceil() as i64
+    }
+
+    fn play_step(&mut self, synth: &mut Synth, step_start: f64) {
+        let length = self.length.clamp(1, MAX_STEPS) as i64;
+        let step = self.steps[self.next_step.rem_euclid(length) as usize];
+        let transpose = self
+            .held
+            .notes()
+            .last()
+            .map_or(0, |(note, _)| note - TRANSPOSE_ROOT);
+        let note = step.note + transpose;
+
+        if !step.on || !(0..128).contains(&note) {
+            self.release(synth);
+            return;
+        }
+
+        // The previous note is only still sounding when it slides into this one
+        let slide_from = self.sounding.take();
+
+        if slide_from != Some(note) {
+            // Only slides glide, whatever the Glide Mode is
+            let glide_mode = std::mem::replace(
+                &mut synth.glide_mode,
+                if slide_from.is_some() {
+                    crate::GlideMode::Legato
+                } else {
+                    crate::GlideMode::Off
+                },
+            );
+
+            synth.accent = if step.accent { self.accent } else { 0.0 };
+            synth.note_voice_id = None;
+            synth.note_on(note, step.velocity);
+            synth.accent = 0.0;
+            synth.glide_mode = glide_mode;
+
+            // Released after the new note so the mono voice carries on legato style. In poly
+            // mode the new note has its own voice and only the pitch glides.
+            if let Some(previous) = slide_from {
+                synth.note_off(previous);
+            }
+        }
+
+        self.sounding = Some(note);
+        self.note_off_at = if step.slide {
+            None
+        } else {
+            Some(step_start + step.gate.clamp(0.01, 1.0) as f64 * self.rate)
+        };
+    }
+}
@@ -1,11 +1,36 @@
+use crate::chord_memory::MAX_CHORD_NOTES;
+use crate::fx_rack::FxRack;
+use crate::limiter::{DcBlocker, Limiter};
 use crate::noise_generator::NoiseGenerator;
+use crate::note_stack::NoteStack;
 use crate::voice::Voice;
 use crate::RX11Params;
 
-pub const MAX_VOICES: usize = 8;
+pub const MAX_VOICES: usize = 32;
 pub const ANALOG: f32 = 0.002;
-pub const SUSTAIN: i32 = -1;
+/// CC64 values from here up to `FULL_PEDAL` are a half pressed sustain pedal
+pub const HALF_PEDAL: f32 = 0.1;
+pub const FULL_PEDAL: f32 = 0.5;
+/// How much longer the release gets just below a fully pressed sustain pedal
+pub const HALF_PEDAL_STRETCH: f32 = 50.0;
 pub const LFO_MAX: f32 = 32.0;
+/// Time in seconds a stolen voice takes to fade out before the new note starts
+pub const STEAL_FADE_TIME: f32 = 0.002;
+
+// The period multiplier per LFO update for the linear glide types, 0 means exponential glide
+pub fn glide_ratio(
+    glide_type: &crate::GlideType,
+    glide_updates: f32,
+    period: f32,
+    target_period: f32,
+) -> f32 {
+    match glide_type {
+        crate::GlideType::Exponential => 0.0,
+        _ if glide_updates < 1.0 => f32::INFINITY,
+        crate::GlideType::ConstantTime => ((target_period / period).ln().abs() / glide_updates).exp(),
+        crate::GlideType::ConstantRate => (std::f32::consts::LN_2 / glide_updates).exp(),
+    }
+}
 
 pub struct Synth {
     pub noise_mix: f32,
@@ -18,6 +43,11 @@ pub struct Synth {
     pub detune: f32,
     pub tune: f32,
     pub pitch_bend: f32,
+    pub pitch_bend_amount: f32,
+    pub pitch_bend_semis: f32,
+    pub bend_range_up: f32,
+    pub bend_range_down: f32,
+    pub bend_smoothing: f32,
     pub volume_trim: f32,
     pub velocity_sensitivity: f32,
     pub vibrato: f32,
@@ -26,9 +56,14 @@ pub struct Synth {
     pub lfo: f32,
     pub lfo_step: i32,
     pub mod_wheel: f32,
+    pub mod_wheel_vibrato: f32,
+    pub mod_wheel_cutoff: f32,
+    pub mod_wheel_pwm: f32,
     pub glide_mode: crate::GlideMode,
     pub glide_rate: f32,
     pub glide_bend: f32,
+    pub glide_type: crate::GlideType,
+    pub glide_updates: f32,
     pub last_note: i32,
     pub filter_key_tracking: f32,
     pub filter_resonance: f32,
@@ -41,10 +76,74 @@ pub struct Synth {
     pub filter_release: f32,
     pub filter_env_depth: f32,
     pub pressure: f32,
+    pub aftertouch_vibrato: f32,
+    pub aftertouch_cutoff: f32,
+    pub aftertouch_cutoff_lfo: bool,
+    pub aftertouch_volume: f32,
+    pub pressure_gain: f32,
+    /// Gain from the CC7 main volume and CC11 expression controllers, and the CC10 pan in -1..1.
+    /// These are separate from the Output Level so a sequencer can balance the synth.
+    pub midi_volume: f32,
+    pub midi_expression: f32,
+    pub midi_pan: f32,
+    midi_gain_left: f32,
+    midi_gain_right: f32,
     pub num_voices: usize,
+    /// MIDI channel and host voice ID of the note event currently being handled
+    pub note_channel: u8,
+    pub note_voice_id: Option<i32>,
+    /// Filter envelope depth added to the notes started next, the sequencer's accent
+    pub accent: f32,
+    pub pan: f32,
+    /// How much the note number pans the voices, 0..1
+    pub key_pan_amount: f32,
+    /// How far apart the pan spread puts the voices, 0..1
+    pub pan_spread: f32,
+    pub pan_spread_mode: crate::PanSpreadMode,
+    pan_spread_seed: u32,
+    /// Stereo width of the output, 0 is mono and 2 is twice as wide
+    pub stereo_width: f32,
+    stereo_width_smoothing: f32,
+    pub mpe_zone: crate::MpeZone,
+    pub mpe_members: u8,
+    pub mpe_bend_range: f32,
+    pub mpe_timbre_cutoff: f32,
+    /// Per channel pitch bend in semitones, pressure and timbre from MPE member channels
+    pub channel_bend: [f32; 16],
+    pub channel_pressure: [f32; 16],
+    pub channel_timbre: [f32; 16],
+    pub poly_mode: crate::PolyMode,
+    pub voice_steal_mode: crate::VoiceStealMode,
+    pub steal_fade_step: f32,
+    /// Program changes fade the output out and back in around loading the preset
+    pub program_fade_out: bool,
+    program_fade: f32,
+    pub voice_counter: u64,
+    /// Pitch of every MIDI note in MIDI note numbers, NaN for keys the tuning leaves unmapped
+    pub tuning_table: [f32; 128],
+    /// Generation of the `Tuning` in the table, 0 is 12-TET
+    pub tuning_generation: u32,
     pub is_sustained: bool,
+    /// How far the sustain pedal is pressed, 0..1 within the half pedal range
+    pub sustain_depth: f32,
+    pub is_sostenuto: bool,
+    /// CC67, lowers the velocity and the filter cutoff of the notes
+    pub soft_pedal: f32,
     pub ignore_velocity: bool,
+    pub note_priority: crate::NotePriority,
+    pub mono_retrigger: bool,
+    pub held_notes: NoteStack,
+    /// Chord memory intervals in semitones above the key
+    chord: [i32; MAX_CHORD_NOTES],
+    chord_len: usize,
+    /// Turns the chord memory off without forgetting the chord, e.g. while a new one is learned
+    pub chord_enabled: bool,
+    /// Keys that were played as chords, so their note offs release the same notes
+    chorded_keys: [bool; 128],
     noise_gen: NoiseGenerator,
+    pub fx_rack: FxRack,
+    dc_blocker: DcBlocker,
+    pub limiter: Limiter,
     pub voices: [Voice; MAX_VOICES],
 }
 
@@ -61,6 +160,11 @@ impl Synth {
             detune: 0.0,
             tune: 0.0,
             pitch_bend: 1.0,
+            pitch_bend_amount: 0.0,
+            pitch_bend_semis: 0.0,
+            bend_range_up: 2.0,
+            bend_range_down: 2.0,
+            bend_smoothing: 1.0,
             volume_trim: 1.0,
             vibrato: 0.0,
             pwm_depth: 0.0,
@@ -70,9 +174,14 @@ impl Synth {
             lfo_phase_increment: 0.0,
             velocity_sensitivity: 0.0,
             mod_wheel: 0.0,
+            mod_wheel_vibrato: 0.0,
+            mod_wheel_cutoff: 0.0,
+            mod_wheel_pwm: 0.0,
             glide_mode: crate::GlideMode::Off,
             glide_rate: 1.0,
             glide_bend: 0.0,
+            glide_type: crate::GlideType::Exponential,
+            glide_updates: 0.0,
             filter_key_tracking: 0.0,
             filter_resonance: 0.0,
             filter_lfo_depth: 0.0,
@@ -84,10 +193,58 @@ impl Synth {
             filter_release: 0.0,
             filter_env_depth: 0.0,
             pressure: 0.0,
+            aftertouch_vibrato: 0.0,
+            aftertouch_cutoff: 0.0,
+            aftertouch_cutoff_lfo: true,
+            aftertouch_volume: 0.0,
+            pressure_gain: 1.0,
+            midi_volume: 1.0,
+            midi_expression: 1.0,
+            midi_pan: 0.0,
+            midi_gain_left: 1.0,
+            midi_gain_right: 1.0,
             num_voices: 1,
+            note_channel: 0,
+            note_voice_id: None,
+            accent: 0.0,
+            pan: 0.0,
+            key_pan_amount: 1.0,
+            pan_spread: 0.0,
+            pan_spread_mode: crate::PanSpreadMode::Alternate,
+            pan_spread_seed: 22222,
+            stereo_width: 1.0,
+            stereo_width_smoothing: 1.0,
+            mpe_zone: crate::MpeZone::Off,
+            mpe_members: 15,
+            mpe_bend_range: 48.0,
+            mpe_timbre_cutoff: 0.0,
+            channel_bend: [0.0; 16],
+            channel_pressure: [0.0; 16],
+            channel_timbre: [0.0; 16],
+            poly_mode: crate::PolyMode::Poly,
+            voice_steal_mode: crate::VoiceStealMode::Quietest,
+            steal_fade_step: 1.0,
+            program_fade_out: false,
+            program_fade: 1.0,
+            voice_counter: 0,
+            tuning_table: crate::tuning::equal_temperament(),
+            tuning_generation: 0,
             is_sustained: false,
+            sustain_depth: 0.0,
+            is_sostenuto: false,
+            soft_pedal: 0.0,
             ignore_velocity: false,
+            note_priority: crate::NotePriority::Last,
+            mono_retrigger: false,
+            held_notes: NoteStack::new(),
+            chord: [0; MAX_CHORD_NOTES],
+            chord_len: 0,
+            chord_enabled: false,
+            chorded_keys: [false; 128],
             noise_gen: NoiseGenerator::new(),
+            fx_rack: FxRack::new(),
+            dc_blocker: DcBlocker::new(),
+            limiter: Limiter::new(),
             voices: Default::default(),
         }
     }
@@ -100,110 +257,536 @@ impl Synth {
         }
 
         self.noise_gen.reset();
+        self.fx_rack.reset();
+        self.dc_blocker.reset();
+        self.limiter.reset();
+        self.held_notes.clear();
+        self.chorded_keys = [false; 128];
         self.pitch_bend = 1.0;
+        self.pitch_bend_amount = 0.0;
+        self.pitch_bend_semis = 0.0;
         self.is_sustained = false;
+        self.sustain_depth = 0.0;
+        self.is_sostenuto = false;
+        self.soft_pedal = 0.0;
         self.mod_wheel = 0.0;
         self.lfo = 0.0;
         self.lfo_step = 0;
         self.last_note = 0;
         self.filter_ctrl = 0.0;
         self.pressure = 0.0;
+        self.pressure_gain = 1.0;
         self.filter_smoothing = 0.0;
+        self.midi_volume = 1.0;
+        self.midi_expression = 1.0;
+        self.midi_pan = 0.0;
+        self.midi_gain_left = 1.0;
+        self.midi_gain_right = 1.0;
+        self.program_fade = 1.0;
+        self.program_fade_out = false;
+        self.reset_channel_expression();
 
         params.output_level.smoothed.reset(0.05);
     }
 
+    pub fn set_mpe_zone(&mut self, mpe_zone: crate::MpeZone, mpe_members: u8) {
+        if mpe_zone != self.mpe_zone || mpe_members != self.mpe_members {
+            self.mpe_zone = mpe_zone;
+            self.mpe_members = mpe_members;
+            self.reset_channel_expression();
+        }
+    }
+
+    pub fn reset_channel_expression(&mut self) {
+        self.channel_bend = [0.0; 16];
+        self.channel_pressure = [0.0; 16];
+        self.channel_timbre = [0.0; 16];
+    }
+
+    // Finds the voice playing a note, by host voice ID if there is one
+    pub fn find_voice(&self, voice_id: Option<i32>, channel: u8, note: u8) -> Option<usize> {
+        match voice_id {
+            Some(voice_id) => self
+                .voices
+                .iter()
+                .position(|voice| voice.voice_id == Some(voice_id)),
+            None => self.voices.iter().position(|voice| {
+                voice.voice_id.is_some() && voice.channel == channel && voice.key == note as i32
+            }),
+        }
+    }
+
+    // Polyphonic aftertouch applies to every voice still holding the note, there can be more than one
+    // when the same key is played again before the previous note has finished its release
+    pub fn poly_pressure(&mut self, voice_id: Option<i32>, channel: u8, note: u8, pressure: f32) {
+        if voice_id.is_some() {
+            if let Some(voice_idx) = self.find_voice(voice_id, channel, note) {
+                self.voices[voice_idx].pressure = pressure * pressure;
+            }
+            return;
+        }
+
+        for voice in &mut self.voices {
+            if voice.voice_id.is_some() && voice.channel == channel && voice.key == note as i32 {
+                voice.pressure = pressure * pressure;
+            }
+        }
+    }
+
+    // Whether the channel (0..16) is one of the member channels of the MPE zone
+    pub fn is_mpe_member(&self, channel: u8) -> bool {
+        match self.mpe_zone {
+            crate::MpeZone::Off => false,
+            crate::MpeZone::Lower => (1..=self.mpe_members).contains(&channel),
+            crate::MpeZone::Upper => ((15 - self.mpe_members)..=14).contains(&channel),
+        }
+    }
+
+    // Switching between modes leaves voices in states the other modes don't expect, so start over
+    pub fn set_poly_mode(&mut self, poly_mode: crate::PolyMode) {
+        if poly_mode != self.poly_mode {
+            for voice in &mut self.voices {
+                voice.release();
+                voice.note = 0;
+                voice.duo = false;
+                voice.gated = false;
+            }
+
+            self.held_notes.clear();
+            self.poly_mode = poly_mode;
+        }
+    }
+
+    // Lets go of every note, for when the note offs of the keys that started them will go somewhere
+    // else, like the arpeggiator being turned on while keys are held
+    pub fn release_all(&mut self) {
+        for voice in &mut self.voices {
+            voice.release_stolen_note();
+            voice.sustained = false;
+            voice.sostenuto = false;
+
+            if voice.note > 0 {
+                voice.release();
+                voice.note = 0;
+            }
+        }
+
+        self.held_notes.clear();
+        self.chorded_keys = [false; 128];
+    }
+
+    // Releases the voices above the new voice count when the polyphony is lowered
+    pub fn set_polyphony(&mut self, num_voices: usize) {
+        if num_voices < self.num_voices {
+            for voice in &mut self.voices[num_voices..] {
+                if voice.note != 0 {
+                    voice.release();
+                    voice.note = 0;
+                }
+            }
+        }
+
+        self.num_voices = num_voices;
+    }
+
     pub fn note_on(&mut self, note: i32, velocity: f32) {
+        let is_chorded = self.chord_enabled && self.chord_len > 0;
+        self.chorded_keys[note.clamp(0, 127) as usize] = is_chorded;
+
+        if !is_chorded {
+            self.key_note_on(note, velocity);
+            return;
+        }
+
+        // Chord memory plays the whole chord on top of the key
+        for idx in 0..self.chord_len {
+            let chord_note = note + self.chord[idx];
+            if chord_note <= 127 {
+                self.key_note_on(chord_note, velocity);
+            }
+
+            // The host's voice ID belongs to the key's own note
+            self.note_voice_id = None;
+        }
+    }
+
+    fn key_note_on(&mut self, note: i32, velocity: f32) {
         let mut velocity = velocity; // Shadow the variable so it can be mutateble without changing the signature
 
+        // Keys the tuning doesn't map don't play
+        if self.note_pitch(note).is_nan() {
+            return;
+        }
+
         if self.ignore_velocity {
             velocity = 80.0;
         }
 
+        // The soft pedal plays notes up to a third quieter
+        velocity *= 1.0 - 0.3 * self.soft_pedal;
+
+        match self.poly_mode {
+            crate::PolyMode::Duo => {
+                self.duo_note_on(note, velocity);
+                return;
+            }
+            crate::PolyMode::Paraphonic => {
+                self.paraphonic_note_on(note, velocity);
+                return;
+            }
+            _ => {}
+        }
+
         let mut voice_idx: usize = 0;
 
         if self.num_voices == 1 {
             // MONOPHONIC
-            if self.voices[0].note > 0 {
-                // Legato style
-                self.shift_queued_notes();
-                self.restart_mono_voice(note, velocity);
+            let sounding = self.held_notes.priority_note(&self.note_priority);
+            self.held_notes.push(note, velocity);
+
+            if let Some((sounding_note, _)) = sounding {
+                // A key is already held, the new note only takes over if it wins the priority
+                if let Some((next_note, next_velocity)) =
+                    self.held_notes.priority_note(&self.note_priority)
+                {
+                    if next_note != sounding_note {
+                        self.play_mono_note(next_note, next_velocity);
+                    }
+                }
                 return;
             }
         } else {
             // POLYPHONIC
-            voice_idx = self.find_free_voice();
+            voice_idx = self.find_free_voice(note);
+
+            // Fade out whatever the voice is playing to avoid a click, the note starts in render
+            // once the fade is done.
+            if self.voices[voice_idx].envelope.is_active() {
+                self.voices[voice_idx].steal(
+                    note,
+                    velocity,
+                    self.note_channel,
+                    self.note_voice_id,
+                    self.accent,
+                    self.steal_fade_step,
+                );
+                return;
+            }
         }
 
+        self.voices[voice_idx].begin_note(self.note_channel, self.note_voice_id, self.accent, note);
         self.start_voice(voice_idx, note, velocity);
     }
 
-    // FIXME: Add test cases
-    pub fn shift_queued_notes(&mut self) {
-        for tmp in (1..(MAX_VOICES - 1)).rev() {
-            self.voices[tmp].note = self.voices[tmp - 1].note;
+    // Switches the mono voice to another held note, either legato style or by retriggering the envelopes
+    pub fn play_mono_note(&mut self, note: i32, velocity: f32) {
+        self.voices[0].begin_note(self.note_channel, self.note_voice_id, self.accent, note);
+
+        if self.mono_retrigger {
+            self.start_voice(0, note, velocity);
+        } else {
+            self.restart_mono_voice(note, velocity);
         }
     }
 
-    // FIXME: Add test cases
-    pub fn next_queued_note(&mut self) -> i32 {
-        let mut held: usize = 0;
+    // DUOPHONIC: the lowest held note plays on oscillator 1 and the highest on oscillator 2
+    pub fn duo_note_on(&mut self, note: i32, velocity: f32) {
+        let is_legato = !self.held_notes.is_empty();
+        self.held_notes.push(note, velocity);
 
-        for i in (0..MAX_VOICES).rev() {
-            if self.voices[i].note > 0 {
-                held = i;
+        if is_legato {
+            self.update_duo_notes(-1.0);
+        } else {
+            self.voices[0].begin_note(self.note_channel, self.note_voice_id, self.accent, note);
+            self.start_voice(0, note, velocity);
+            self.voices[0].duo_period = self.voices[0].period;
+            self.update_duo_notes(velocity);
+        }
+    }
+
+    pub fn update_duo_notes(&mut self, velocity: f32) {
+        let (Some((low, _)), Some((high, _))) = (
+            self.held_notes.priority_note(&crate::NotePriority::Lowest),
+            self.held_notes.priority_note(&crate::NotePriority::Highest),
+        ) else {
+            return;
+        };
+
+        if self.voices[0].note != low {
+            self.restart_mono_voice(low, velocity);
+        }
+
+        let period = self.calculate_period(0, high);
+        let voice = &mut self.voices[0];
+        voice.duo = true;
+
+        if voice.duo_target_period != period {
+            voice.duo_glide_ratio =
+                glide_ratio(&self.glide_type, self.glide_updates, voice.duo_period, period);
+            voice.duo_target_period = period;
+        }
+
+        if self.glide_mode == crate::GlideMode::Off {
+            voice.duo_period = period;
+        }
+    }
+
+    // PARAPHONIC: every note gets its own oscillators, which are all mixed into voice 0 to share
+    // its filter and envelopes.
+    pub fn paraphonic_note_on(&mut self, note: i32, velocity: f32) {
+        let slots = 1..(self.num_voices + 1).min(MAX_VOICES);
+
+        if self.voices[0].note == 0 {
+            // New phrase, the oscillators still ringing out from the last one fade out while the
+            // carrier's envelope starts again from where its release got to
+            for voice in &mut self.voices[slots.clone()] {
+                if voice.gated {
+                    voice.fade_out_slot(self.steal_fade_step);
+                }
+            }
+
+            // The carrier isn't a note the host knows about, so it doesn't keep a voice ID
+            self.voices[0].begin_note(self.note_channel, None, self.accent, note);
+            self.voices[0].voice_id = None;
+            self.voices[0].stolen_voice_id = None;
+            self.start_voice(0, note, velocity);
+
+            let carrier = &mut self.voices[0];
+            carrier.oscillator_1.amplitude = 0.0;
+            carrier.oscillator_2.amplitude = 0.0;
+            carrier.key_pan = 0.0;
+        }
+
+        self.held_notes.push(note, velocity);
+
+        let voice_idx = self.voices[slots.clone()]
+            .iter()
+            .position(|voice| !voice.gated)
+            .or_else(|| {
+                self.voices[slots.clone()]
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, voice)| voice.age)
+                    .map(|(idx, _)| idx)
+            })
+            .map_or(slots.start, |idx| idx + slots.start);
+
+        self.voices[voice_idx].begin_note(self.note_channel, self.note_voice_id, self.accent, note);
+        self.start_voice(voice_idx, note, velocity);
+
+        // The carrier's envelope shapes every slot, the slot's own would never be advanced and
+        // keep the voice looking active forever
+        let voice = &mut self.voices[voice_idx];
+        voice.envelope.reset();
+        voice.filter_envelope.reset();
+        voice.gated = true;
+    }
+
+    pub fn paraphonic_note_off(&mut self, note: i32) {
+        self.held_notes.remove(note);
+        let is_last_note = self.held_notes.is_empty();
+
+        // With MPE the same note can play on several channels, so note offs have to match the channel
+        let any_channel = self.mpe_zone == crate::MpeZone::Off;
+
+        for voice in &mut self.voices[1..] {
+            if voice.gated
+                && voice.note == note
+                && !voice.sustained
+                && (any_channel || voice.channel == self.note_channel)
+            {
+                if self.is_sustained || voice.sostenuto {
+                    voice.sustained = true;
+                } else {
+                    Self::release_paraphonic_slot(voice, is_last_note);
+                }
             }
         }
 
-        if held > 0 {
-            let note = self.voices[held].note;
-            self.voices[held].note = 0;
-            return note;
+        self.release_paraphonic_carrier();
+    }
+
+    fn release_paraphonic_slot(voice: &mut Voice, is_last_note: bool) {
+        voice.sustained = false;
+        voice.sostenuto = false;
+        voice.note = 0;
+
+        // The last notes keep sounding while the shared envelope releases
+        if !is_last_note {
+            voice.gated = false;
+            voice.envelope.reset();
         }
+    }
 
-        0
+    fn release_paraphonic_carrier(&mut self) {
+        let is_sustaining = self.voices[1..]
+            .iter()
+            .any(|voice| voice.gated && voice.sustained);
+
+        if self.held_notes.is_empty() && !is_sustaining && self.voices[0].note != 0 {
+            self.voices[0].release();
+            self.voices[0].note = 0;
+        }
+    }
+
+    // CC64. Past the half pedal range the notes are held like before, inside it they fade out
+    // slower the further down the pedal is.
+    pub fn set_sustain_pedal(&mut self, value: f32) {
+        self.is_sustained = value >= HALF_PEDAL;
+        self.sustain_depth = ((value - HALF_PEDAL) / (FULL_PEDAL - HALF_PEDAL)).clamp(0.0, 1.0);
+        self.update_sustained_voices();
+    }
+
+    // CC66. Only the notes held when the pedal goes down are sustained by it.
+    pub fn set_sostenuto_pedal(&mut self, is_down: bool) {
+        if is_down == self.is_sostenuto {
+            return;
+        }
+
+        self.is_sostenuto = is_down;
+
+        for voice in &mut self.voices {
+            if is_down {
+                voice.sostenuto = voice.note > 0 && !voice.sustained;
+            } else {
+                voice.sostenuto = false;
+            }
+        }
+
+        self.update_sustained_voices();
+    }
+
+    // Releases the notes no pedal holds anymore and applies the half pedal to the rest
+    fn update_sustained_voices(&mut self) {
+        if self.poly_mode == crate::PolyMode::Paraphonic {
+            let is_last_note = self.held_notes.is_empty();
+
+            for voice in &mut self.voices[1..] {
+                if voice.sustained && !self.is_sustained && !voice.sostenuto {
+                    Self::release_paraphonic_slot(voice, is_last_note);
+                }
+            }
+
+            self.release_paraphonic_carrier();
+            return;
+        }
+
+        let half_pedal_release = self
+            .env_release
+            .powf(1.0 / (1.0 + HALF_PEDAL_STRETCH * self.sustain_depth * self.sustain_depth));
+
+        for voice in &mut self.voices {
+            if !voice.sustained || voice.sostenuto {
+                continue;
+            }
+
+            if !self.is_sustained {
+                voice.release_pedal(self.env_release);
+            } else if self.sustain_depth < 1.0 {
+                voice.damp(half_pedal_release);
+            } else if voice.damped {
+                voice.damp(1.0);
+            }
+        }
+    }
+
+    // Picks the voice for a new note. A silent voice is always used first, otherwise one is stolen
+    // according to the voice steal mode. Voices already fading out for a stolen note are skipped
+    // unless every voice is.
+    pub fn find_free_voice(&self, note: i32) -> usize {
+        let voices = &self.voices[..self.num_voices];
+        let candidates = || {
+            voices
+                .iter()
+                .enumerate()
+                .filter(|(_, voice)| !voice.is_stealing())
+        };
+
+        if let Some((voice_idx, _)) = candidates().find(|(_, voice)| !voice.envelope.is_active()) {
+            return voice_idx;
+        }
+
+        match self.voice_steal_mode {
+            crate::VoiceStealMode::Quietest => self.find_quietest_voice(|_| true),
+            crate::VoiceStealMode::Oldest => self.find_oldest_voice(),
+            // The target period is used instead of the note so sustained voices are included
+            crate::VoiceStealMode::Lowest => candidates()
+                .max_by(|(_, a), (_, b)| a.target_period.total_cmp(&b.target_period))
+                .map(|(idx, _)| idx)
+                .unwrap_or(0),
+            crate::VoiceStealMode::Highest => candidates()
+                .min_by(|(_, a), (_, b)| a.target_period.total_cmp(&b.target_period))
+                .map(|(idx, _)| idx)
+                .unwrap_or(0),
+            // The key is kept after the note is released, so a released note is reused as well
+            crate::VoiceStealMode::SameNote => candidates()
+                .find(|(_, voice)| voice.key == note)
+                .map(|(idx, _)| idx)
+                .unwrap_or_else(|| self.find_quietest_voice(|_| true)),
+            crate::VoiceStealMode::Released => {
+                let released = self.find_quietest_voice(|voice| voice.note == 0);
+
+                if voices[released].note == 0 && !voices[released].is_stealing() {
+                    released
+                } else {
+                    self.find_quietest_voice(|_| true)
+                }
+            }
+        }
     }
 
-    // Finds the quietest voice not in attack
-    // TODO - I wish I could do this with Option<&mut Voice>, but I'm having mut borrow issues
-    // From Rust playground, I think this can be achieved if the method is not implemented on the synth. Maybe a Voices
-    // type is worth it?
+    // Finds the quietest voice not in attack out of the voices matching the filter
     //
     // Notes:
     // This allows the same note to be played in multiple voices if the same note is played in succession multiple times.
-    // Some voice stealing ideas.
-    // If same note was playing, reuse the voice
-    // Try to steal released notes
-    // Steal the note w/ smallest amplitude or velocity
-    // Steal the oldest note which isn't either the highest or lowest note being played
-    pub fn find_free_voice(&mut self) -> usize {
-        let mut voice_idx: usize = 0;
+    pub fn find_quietest_voice(&self, filter: impl Fn(&Voice) -> bool) -> usize {
+        let mut voice_idx = None;
         let mut loudness = 100.0; // Louder than any envelope
 
-        for (idx, voice) in self.voices.iter().enumerate() {
-            if voice.envelope.level < loudness && !voice.envelope.is_in_attack() {
+        for (idx, voice) in self.voices[..self.num_voices].iter().enumerate() {
+            if filter(voice)
+                && !voice.is_stealing()
+                && voice.envelope.level < loudness
+                && !voice.envelope.is_in_attack()
+            {
                 loudness = voice.envelope.level;
-                voice_idx = idx;
+                voice_idx = Some(idx);
             }
         }
 
-        voice_idx
+        // When every voice is still in its attack the oldest one is stolen instead
+        voice_idx.unwrap_or_else(|| self.find_oldest_voice())
+    }
+
+    // Finds the oldest voice that isn't already fading out for a stolen note
+    pub fn find_oldest_voice(&self) -> usize {
+        self.voices[..self.num_voices]
+            .iter()
+            .enumerate()
+            .filter(|(_, voice)| !voice.is_stealing())
+            .min_by_key(|(_, voice)| voice.age)
+            .map(|(idx, _)| idx)
+            .unwrap_or(0)
     }
 
     pub fn start_voice(&mut self, voice_idx: usize, note: i32, velocity: f32) {
         let period = self.calculate_period(voice_idx, note);
         let is_playing_legato_style = self.is_playing_legato_style();
+        // In semitones, microtuned notes can be a fraction of one apart
+        let last_note_distance = self.note_pitch(note) - self.note_pitch(self.last_note);
+        let spread_pan = self.next_spread_pan();
 
         let voice = &mut self.voices[voice_idx];
+        voice.cancel_steal();
         voice.target_period = period;
 
         // Glide
-        let mut note_distance = 0;
-        if self.last_note > 0 {
+        let mut note_distance = 0.0;
+        if self.last_note > 0 && last_note_distance.is_finite() {
             if self.glide_mode == crate::GlideMode::Legato
                 || (self.glide_mode == crate::GlideMode::Always && is_playing_legato_style)
             {
-                note_distance = note - self.last_note;
+                note_distance = last_note_distance;
             }
         }
 
@@ -216,15 +799,23 @@ impl Synth {
             voice.cutoff_freq *= (self.velocity_sensitivity * (velocity - 64.0)).exp();
         }
 
-        voice.period = period * 1.059463094359_f32.powf(note_distance as f32 - self.glide_bend);
+        voice.period = period * 1.059463094359_f32.powf(note_distance - self.glide_bend);
 
         if voice.period < 6.0 {
             voice.period = 6.0;
         }
 
+        voice.glide_ratio = glide_ratio(&self.glide_type, self.glide_updates, voice.period, period);
+
         self.last_note = note;
+        self.voice_counter += 1;
+        voice.age = self.voice_counter;
         voice.note = note;
-        voice.update_panning();
+        voice.sustained = false;
+        voice.sostenuto = false;
+        voice.damped = false;
+        voice.spread_pan = spread_pan;
+        voice.update_panning(self.key_pan_amount);
 
         // Adjust velocity to be non-linear - somewhat parabolic
         let velocity = 0.004 * (velocity + 64.0) * (velocity + 64.0) - 8.0;
@@ -255,29 +846,141 @@ impl Synth {
     }
 
     pub fn note_off(&mut self, note: i32) {
-        if self.num_voices == 1 && self.voices[0].note == note {
-            let queued_note = self.next_queued_note();
+        if !std::mem::take(&mut self.chorded_keys[note.clamp(0, 127) as usize]) {
+            self.key_note_off(note);
+            return;
+        }
+
+        for idx in 0..self.chord_len {
+            let chord_note = note + self.chord[idx];
+            if chord_note <= 127 {
+                self.key_note_off(chord_note);
+            }
+        }
+    }
+
+    /// Sets the chord memory's intervals, an empty chord plays the keys as they are. Turning the chord
+    /// memory on or off with `chord_enabled` leaves the chord as it is.
+    pub fn set_chord(&mut self, intervals: &[i32]) {
+        let len = intervals.len().min(MAX_CHORD_NOTES);
+        if self.chord[..self.chord_len] == intervals[..len] {
+            return;
+        }
+
+        // The held notes were started with the old chord, note offs wouldn't find them anymore
+        for voice in &mut self.voices {
+            if voice.note > 0 {
+                voice.release();
+                voice.note = 0;
+            }
+        }
+        self.held_notes.clear();
+        self.chorded_keys = [false; 128];
+
+        self.chord[..len].copy_from_slice(&intervals[..len]);
+        self.chord_len = len;
+    }
+
+    fn key_note_off(&mut self, note: i32) {
+        if self.poly_mode == crate::PolyMode::Paraphonic {
+            self.paraphonic_note_off(note);
+            return;
+        }
 
-            if queued_note > 0 {
-                self.restart_mono_voice(queued_note, -1.0);
+        if self.poly_mode == crate::PolyMode::Duo {
+            self.held_notes.remove(note);
+
+            if !self.held_notes.is_empty() {
+                self.update_duo_notes(-1.0);
+                return;
+            }
+
+            // Let the release below find the voice, whichever of the two notes was the last one
+            if self.voices[0].note > 0 {
+                self.voices[0].note = note;
+            }
+        } else if self.num_voices == 1 {
+            let sounding = self.held_notes.priority_note(&self.note_priority);
+            self.held_notes.remove(note);
+
+            if self.voices[0].note == note && sounding.is_some_and(|(n, _)| n == note) {
+                if let Some((next_note, next_velocity)) =
+                    self.held_notes.priority_note(&self.note_priority)
+                {
+                    // Legato keeps the filter cutoff of the previous note
+                    let velocity = if self.mono_retrigger { next_velocity } else { -1.0 };
+                    self.play_mono_note(next_note, velocity);
+                }
             }
         }
+
+        // With MPE the same note can play on several channels, so note offs have to match the channel
+        let any_channel = self.mpe_zone == crate::MpeZone::Off;
+        let mut is_sustaining = false;
+
         for voice in &mut self.voices {
-            if voice.note == note {
-                if self.is_sustained {
-                    voice.note = SUSTAIN;
+            // A stolen voice is still on the channel of the note fading out
+            voice.release_steal(note, (!any_channel).then_some(self.note_channel));
+
+            if !(any_channel || voice.channel == self.note_channel) {
+                continue;
+            }
+
+            if voice.note == note && !voice.sustained {
+                if self.is_sustained || voice.sostenuto {
+                    voice.sustained = true;
+                    is_sustaining = true;
                 } else {
                     voice.release();
                     voice.note = 0;
                 }
             }
         }
+
+        if is_sustaining {
+            self.update_sustained_voices();
+        }
+    }
+
+    // The pitch of the note in the current tuning, in MIDI note numbers
+    pub fn note_pitch(&self, note: i32) -> f32 {
+        self.tuning_table[note.clamp(0, 127) as usize]
+    }
+
+    pub fn set_tuning(&mut self, tuning: Option<&crate::tuning::Tuning>) {
+        match tuning {
+            Some(tuning) => {
+                self.tuning_table = tuning.table();
+                self.tuning_generation = tuning.generation;
+            }
+            None => {
+                self.tuning_table = crate::tuning::equal_temperament();
+                self.tuning_generation = 0;
+            }
+        }
+    }
+
+    // MIDI Tuning Standard messages retune notes on top of the current tuning table
+    pub fn apply_mts(&mut self, message: &crate::mts::MtsMessage) {
+        for (note, pitch) in message.changes() {
+            self.tuning_table[note] = pitch;
+        }
+
+        // Real-time changes also bend the notes that are already playing
+        if message.realtime {
+            for voice_idx in 0..MAX_VOICES {
+                let note = self.voices[voice_idx].note;
+                if note > 0 {
+                    self.voices[voice_idx].target_period = self.calculate_period(voice_idx, note);
+                }
+            }
+        }
     }
 
     pub fn calculate_period(&self, voice_idx: usize, note: i32) -> f32 {
         // Adding the ANALOG "randomness" will slightly detune the note to make it sound more analog
-        let mut period =
-            self.tune * (-0.05776226505 * (note as f32 + ANALOG * voice_idx as f32)).exp();
+        let pitch = self.note_pitch(note);
+        let mut period = self.tune * (-0.05776226505 * (pitch + ANALOG * voice_idx as f32)).exp();
 
         // Ensure the period for the detuned oscillator is at least six samples long
         while period < 6.0 || period * self.detune < 6.0 {
@@ -297,6 +1000,8 @@ impl Synth {
             voice.period = period;
         }
 
+        voice.glide_ratio = glide_ratio(&self.glide_type, self.glide_updates, voice.period, period);
+
         voice.cutoff_freq = self.sample_rate / (period / std::f32::consts::PI);
 
         if velocity > 0.0 {
@@ -305,7 +1010,28 @@ impl Synth {
 
         voice.envelope.level += crate::envelope::SILENCE + crate::envelope::SILENCE;
         voice.note = note;
-        voice.update_panning();
+        voice.sustained = false;
+        voice.update_panning(self.key_pan_amount);
+    }
+
+    // The side of the stereo field for the next voice, every other note or a random one
+    fn next_spread_pan(&mut self) -> f32 {
+        match self.pan_spread_mode {
+            crate::PanSpreadMode::Alternate => {
+                if self.voice_counter.is_multiple_of(2) {
+                    -1.0
+                } else {
+                    1.0
+                }
+            }
+            crate::PanSpreadMode::Random => {
+                self.pan_spread_seed = self
+                    .pan_spread_seed
+                    .wrapping_mul(196314165)
+                    .wrapping_add(907633515);
+                (self.pan_spread_seed >> 8) as f32 / 8388608.0 - 1.0
+            }
+        }
     }
 
     pub fn update_lfo(&mut self) {
@@ -320,36 +1046,77 @@ impl Synth {
                 self.lfo -= std::f32::consts::TAU;
             }
 
+            // Exponential pitch bend, expressed as a period multiplier
+            let bend_target = if self.pitch_bend_amount >= 0.0 {
+                self.pitch_bend_amount * self.bend_range_up
+            } else {
+                self.pitch_bend_amount * self.bend_range_down
+            };
+            self.pitch_bend_semis += self.bend_smoothing * (bend_target - self.pitch_bend_semis);
+            self.pitch_bend = (-0.05776226505 * self.pitch_bend_semis).exp();
+
             let sine = self.lfo.sin();
-            let vibrato_mod = 1.0 + sine * (self.mod_wheel + self.vibrato);
-            let pwm = 1.0 + sine * (self.mod_wheel + self.pwm_depth);
+            // Aftertouch either deepens the filter LFO or moves the cutoff
+            let pressure_cutoff = if self.aftertouch_cutoff_lfo {
+                self.aftertouch_cutoff * sine
+            } else {
+                self.aftertouch_cutoff
+            };
+            let vibrato_depth = self.mod_wheel * self.mod_wheel_vibrato
+                + self.pressure * self.aftertouch_vibrato
+                + self.vibrato;
+            let pwm = 1.0 + sine * (self.mod_wheel * self.mod_wheel_pwm + self.pwm_depth);
             let filter_mod = self.filter_key_tracking
                 + self.filter_ctrl
-                + (self.filter_lfo_depth + self.pressure) * sine;
+                + self.mod_wheel * self.mod_wheel_cutoff
+                + self.pressure * pressure_cutoff
+                - 0.5 * self.soft_pedal
+                + self.filter_lfo_depth * sine;
 
             // One Pole low pass filter to smooth filter "zipping"
             self.filter_smoothing += 0.005 * (filter_mod - self.filter_smoothing);
 
+            // Same smoothing for the aftertouch volume, which can boost the output up to +6dB
+            let pressure_gain = 1.0 + self.pressure * self.aftertouch_volume;
+            self.pressure_gain += 0.005 * (pressure_gain - self.pressure_gain);
+
             for voice in &mut self.voices {
-                if voice.envelope.is_active() {
-                    voice.oscillator_1.modulation = vibrato_mod;
+                if voice.envelope.is_active() || voice.gated {
+                    // Per note expression from the MPE member channels and polyphonic aftertouch
+                    let channel = voice.channel as usize;
+                    let pressure = self.channel_pressure[channel] + voice.pressure;
+
+                    voice.oscillator_1.modulation = 1.0
+                        + sine
+                            * (vibrato_depth
+                                + pressure * self.aftertouch_vibrato
+                                + 0.08 * voice.vibrato);
                     voice.oscillator_2.modulation = pwm;
-                    voice.filter_mod = self.filter_smoothing;
+                    voice.filter_mod = self.filter_smoothing
+                        + voice.cutoff_mod
+                        + pressure * pressure_cutoff
+                        + (self.channel_timbre[channel] + voice.brightness) * self.mpe_timbre_cutoff;
+                    voice.volume_mod += 0.05 * (pressure * self.aftertouch_volume - voice.volume_mod);
+                    voice.pitch_bend = self.pitch_bend * voice.note_bend(self.channel_bend[channel]);
                     voice.update_lfo();
-
-                    //self.update_period(voice); // TODO: This causes mut borrow issues
-                    voice.oscillator_1.period = voice.period * self.pitch_bend;
-                    voice.oscillator_2.period = voice.oscillator_1.period * self.detune;
+                    voice.update_period(
+                        voice.pitch_bend,
+                        self.detune * (-0.0005776226505 * voice.detune_mod).exp(),
+                    );
                 }
             }
         }
     }
 
+    pub fn is_program_faded_out(&self) -> bool {
+        self.program_fade == 0.0
+    }
+
     pub fn is_playing_legato_style(&self) -> bool {
         let mut held = 0;
 
         for i in 0..MAX_VOICES {
-            if self.voices[i].note > 0 {
+            if self.voices[i].note > 0 && !self.voices[i].sustained {
                 held += 1;
             }
         }
@@ -357,11 +1124,6 @@ impl Synth {
         return held > 0;
     }
 
-    // pub fn update_period(&mut self, voice: &mut Voice) {
-    //     voice.oscillator_1.period = voice.period * self.pitch_bend;
-    //     voice.oscillator_2.period = voice.oscillator_1.period * self.detune;
-    // }
-
     pub fn render(
         &mut self,
         output_buffer: &mut [&mut [f32]],
@@ -370,39 +1132,118 @@ impl Synth {
         params: &RX11Params,
     ) {
         for voice in &mut self.voices {
-            if voice.envelope.is_active() {
-                // Update period
-                voice.oscillator_1.period = voice.period * self.pitch_bend;
-                voice.oscillator_2.period = voice.oscillator_1.period * self.detune;
+            if voice.envelope.is_active() || voice.gated {
+                let note_bend = voice.note_bend(self.channel_bend[voice.channel as usize]);
+                voice.update_period(
+                    self.pitch_bend * note_bend,
+                    self.detune * (-0.0005776226505 * voice.detune_mod).exp(),
+                );
 
                 voice.glide_rate = self.glide_rate;
-                voice.filter_resonance = self.filter_resonance;
-                voice.pitch_bend = self.pitch_bend;
-                voice.filter_env_depth = self.filter_env_depth;
+                voice.filter_resonance = self.filter_resonance * voice.resonance_mod.exp();
+                voice.pitch_bend = self.pitch_bend * note_bend;
+                voice.filter_env_depth = self.filter_env_depth + voice.accent;
+                voice.oscillator_2.amplitude =
+                    voice.oscillator_1.amplitude * (self.osc_mix + voice.osc_mix_mod).clamp(0.0, 1.0);
+                voice.pan_offset =
+                    self.pan + voice.pan_mod + voice.note_pan + self.pan_spread * voice.spread_pan;
+                voice.update_pan_gains();
             }
         }
 
+        // The MIDI pan works as a balance control, the center leaves both sides at full level
+        let midi_gain = self.midi_volume * self.midi_expression;
+        let midi_left = midi_gain * (std::f32::consts::FRAC_PI_2 * self.midi_pan.max(0.0)).cos();
+        let midi_right = midi_gain * (std::f32::consts::FRAC_PI_2 * self.midi_pan.min(0.0)).cos();
+
+        self.dc_blocker.set_sample_rate(self.sample_rate);
+        self.limiter.set_sample_rate(self.sample_rate);
+
         for (_value_idx, sample_idx) in (block_start..block_end).enumerate() {
+            for voice_idx in 0..MAX_VOICES {
+                if self.voices[voice_idx].is_steal_complete() {
+                    let (note, velocity, released) = self.voices[voice_idx].finish_steal();
+                    self.start_voice(voice_idx, note, velocity);
+
+                    // The stolen note is one of the chord's notes, not the key that was played
+                    if released {
+                        self.note_channel = self.voices[voice_idx].channel;
+                        self.key_note_off(note);
+                    }
+                }
+            }
+
             self.update_lfo();
 
             let noise = self.noise_gen.next_value() * self.noise_mix;
             let mut output_left = 0.0;
             let mut output_right = 0.0;
 
-            for voice in &mut self.voices {
-                if voice.envelope.is_active() {
-                    let output_sample = voice.render(noise);
-                    output_left += output_sample * voice.pan_left;
-                    output_right += output_sample * voice.pan_right;
+            if self.poly_mode == crate::PolyMode::Paraphonic {
+                let mut oscillators = 0.0;
+
+                for voice in &mut self.voices[1..] {
+                    if voice.gated {
+                        oscillators += voice.render_slot();
+                    }
+                }
+
+                let carrier = &mut self.voices[0];
+                if carrier.envelope.is_active() {
+                    let output_sample = carrier.render(noise + oscillators);
+                    output_left += output_sample * carrier.pan_left;
+                    output_right += output_sample * carrier.pan_right;
+                } else {
+                    // The released notes have rung out with the shared envelope
+                    for voice in &mut self.voices[1..] {
+                        if voice.note == 0 {
+                            voice.gated = false;
+                        }
+                    }
+                }
+            } else {
+                for voice in &mut self.voices {
+                    if voice.envelope.is_active() {
+                        let output_sample = voice.render(noise);
+                        output_left += output_sample * voice.pan_left;
+                        output_right += output_sample * voice.pan_right;
+                    }
                 }
             }
 
+            (output_left, output_right) = self.fx_rack.process(output_left, output_right);
+
+            // Mid/side width, narrowing it down to mono keeps the basses in the center
+            self.stereo_width_smoothing += 0.001 * (self.stereo_width - self.stereo_width_smoothing);
+            let mid = 0.5 * (output_left + output_right);
+            let side = 0.5 * (output_left - output_right) * self.stereo_width_smoothing;
+            output_left = mid + side;
+            output_right = mid - side;
+
             // TODO: See if there is ever a case where the buffer is "MONO" where
             // there is no right channel. The sample needs to be (output_left + output_right) * 0.5
-            let output_level = params.output_level.smoothed.next();
+            let output_level = params.output_level.smoothed.next() * self.pressure_gain;
             output_left *= output_level;
             output_right *= output_level;
 
+            if self.program_fade_out {
+                self.program_fade = (self.program_fade - self.steal_fade_step).max(0.0);
+            } else {
+                self.program_fade = (self.program_fade + self.steal_fade_step).min(1.0);
+            }
+            output_left *= self.program_fade;
+            output_right *= self.program_fade;
+
+            // Smoothed like the aftertouch volume so the MIDI controllers don't zipper
+            self.midi_gain_left += 0.005 * (midi_left - self.midi_gain_left);
+            self.midi_gain_right += 0.005 * (midi_right - self.midi_gain_right);
+            output_left *= self.midi_gain_left;
+            output_right *= self.midi_gain_right;
+
+            // Master bus, last so nothing after it can push the output over again
+            (output_left, output_right) = self.dc_blocker.process(output_left, output_right);
+            (output_left, output_right) = self.limiter.process(output_left, output_right);
+
             output_buffer[0][sample_idx] = output_left;
             output_buffer[1][sample_idx] = output_right;
         }
@@ -415,3 +1256,321 @@ impl Synth {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two voices with an instant attack and decay, holding at full level
+    fn test_synth() -> Synth {
+        let mut synth = Synth::new();
+        synth.num_voices = 2;
+        synth.tune = 10_000.0; // A period of about 300 samples at middle C
+        synth.detune = 1.0;
+        synth.env_attack = 0.0;
+        synth.env_decay = 0.0;
+        synth.env_sustain = 1.0;
+        synth.env_release = 0.9999;
+        synth.filter_sustain = 1.0;
+        synth.steal_fade_step = 0.01;
+        synth
+    }
+
+    fn render(synth: &mut Synth, params: &RX11Params, samples: usize) {
+        let mut left = vec![0.0; samples];
+        let mut right = vec![0.0; samples];
+        let mut output = [left.as_mut_slice(), right.as_mut_slice()];
+        synth.render(&mut output, 0, samples, params);
+    }
+
+    fn voice_playing(synth: &Synth, note: i32) -> Option<usize> {
+        synth.voices[..synth.num_voices]
+            .iter()
+            .position(|voice| voice.note == note && voice.envelope.is_active())
+    }
+
+    #[test]
+    fn steal_completes_when_the_release_goes_silent() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+        synth.env_release = 0.5;
+        synth.steal_fade_step = 1e-6;
+
+        synth.note_on(60, 100.0);
+        synth.note_on(62, 100.0);
+        render(&mut synth, &params, 2);
+        synth.note_off(60);
+        render(&mut synth, &params, 1);
+
+        synth.note_on(64, 100.0);
+        assert!(synth.voices.iter().any(|voice| voice.is_stealing()));
+
+        render(&mut synth, &params, 200);
+        assert!(voice_playing(&synth, 64).is_some());
+        assert!(!synth.voices.iter().any(|voice| voice.is_stealing()));
+    }
+
+    #[test]
+    fn starting_a_voice_cancels_its_steal() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+
+        synth.note_on(60, 100.0);
+        synth.note_on(62, 100.0);
+        render(&mut synth, &params, 2);
+        synth.note_on(64, 100.0);
+
+        let voice_idx = synth.voices.iter().position(|voice| voice.is_stealing()).unwrap();
+        synth.start_voice(voice_idx, 67, 100.0);
+        assert!(!synth.voices[voice_idx].is_stealing());
+
+        render(&mut synth, &params, 200);
+        assert_eq!(synth.voices[voice_idx].note, 67);
+    }
+
+    #[test]
+    fn stealing_voices_are_not_stolen_again() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+
+        synth.note_on(60, 100.0);
+        synth.note_on(62, 100.0);
+        render(&mut synth, &params, 2);
+        synth.note_on(64, 100.0);
+        synth.note_on(65, 100.0);
+        assert!(synth.voices[..2].iter().all(|voice| voice.is_stealing()));
+
+        render(&mut synth, &params, 200);
+        assert!(voice_playing(&synth, 64).is_some());
+        assert!(voice_playing(&synth, 65).is_some());
+    }
+
+    #[test]
+    fn voices_in_attack_are_stolen_oldest_first() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+        synth.env_attack = 0.999;
+
+        synth.note_on(60, 100.0);
+        synth.note_on(62, 100.0);
+        render(&mut synth, &params, 2);
+        assert!(synth.voices[..2].iter().all(|voice| voice.envelope.is_in_attack()));
+
+        synth.note_on(64, 100.0);
+        synth.note_on(65, 100.0);
+        assert!(synth.voices[..2].iter().all(|voice| voice.is_stealing()));
+
+        render(&mut synth, &params, 200);
+        assert!(voice_playing(&synth, 64).is_some());
+        assert!(voice_playing(&synth, 65).is_some());
+    }
+
+    #[test]
+    fn same_note_reuses_the_released_voice_of_the_key() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+        synth.voice_steal_mode = crate::VoiceStealMode::SameNote;
+
+        synth.note_on(60, 100.0);
+        render(&mut synth, &params, 2);
+        synth.env_sustain = 0.2;
+        synth.note_on(62, 100.0);
+        render(&mut synth, &params, 2);
+        synth.note_off(60);
+        render(&mut synth, &params, 1);
+
+        // The quieter voice playing 62 would be picked by the quietest voice mode
+        let voice_idx = synth.voices.iter().position(|voice| voice.key == 60).unwrap();
+        assert_eq!(synth.find_free_voice(60), voice_idx);
+
+        synth.note_on(60, 100.0);
+        assert!(synth.voices[voice_idx].is_stealing());
+        assert_eq!(synth.voices[voice_idx].key, 60);
+    }
+
+    #[test]
+    fn stolen_voice_keeps_the_old_note_until_the_fade_is_done() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+
+        synth.note_voice_id = Some(1);
+        synth.note_on(60, 100.0);
+        synth.note_voice_id = Some(2);
+        synth.note_on(62, 100.0);
+        render(&mut synth, &params, 2);
+
+        let voice_idx = synth.find_free_voice(64);
+        let old_key = synth.voices[voice_idx].key;
+        let old_voice_id = synth.voices[voice_idx].voice_id;
+        synth.voices[voice_idx].poly_mod[0] = 0.5;
+
+        synth.note_voice_id = Some(3);
+        synth.note_on(64, 100.0);
+        render(&mut synth, &params, 10);
+
+        let voice = &synth.voices[voice_idx];
+        assert!(voice.is_stealing());
+        assert_eq!(voice.key, old_key);
+        assert_eq!(voice.voice_id, old_voice_id);
+        assert_eq!(voice.poly_mod[0], 0.5);
+
+        render(&mut synth, &params, 200);
+
+        let voice = &synth.voices[voice_idx];
+        assert_eq!(voice.key, 64);
+        assert_eq!(voice.voice_id, Some(3));
+        assert_eq!(voice.poly_mod[0], 0.0);
+        assert_eq!(voice.stolen_voice_id.map(|(voice_id, _, _)| voice_id), old_voice_id);
+    }
+
+    #[test]
+    fn note_off_during_the_fade_releases_the_stolen_note() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+
+        synth.note_on(60, 100.0);
+        synth.note_on(62, 100.0);
+        render(&mut synth, &params, 2);
+        synth.note_on(64, 100.0);
+        synth.note_off(64);
+
+        render(&mut synth, &params, 200);
+        assert!(synth.voices.iter().all(|voice| voice.note != 64));
+    }
+
+    #[test]
+    fn turning_the_chord_memory_off_keeps_the_held_chord() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+        synth.num_voices = 4;
+        synth.chord_enabled = true;
+        synth.set_chord(&[0, 4, 7]);
+
+        synth.note_on(60, 100.0);
+        render(&mut synth, &params, 2);
+
+        synth.chord_enabled = false;
+        synth.set_chord(&[0, 4, 7]);
+        for note in [60, 64, 67] {
+            assert!(voice_playing(&synth, note).is_some());
+        }
+
+        // The key was played as a chord, so letting go of it releases the whole chord
+        synth.note_off(60);
+        assert!(synth.voices.iter().all(|voice| voice.note == 0));
+    }
+
+    #[test]
+    fn released_stolen_chord_note_only_releases_that_note() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+        synth.num_voices = 3;
+        synth.chord_enabled = true;
+        synth.set_chord(&[0, 12]);
+
+        synth.note_on(62, 100.0);
+        render(&mut synth, &params, 2);
+
+        // 50 gets the free voice, its octave steals the voice of 62
+        synth.note_on(50, 100.0);
+        synth.note_off(50);
+        render(&mut synth, &params, 200);
+
+        // The held key's octave must not be released along with the stolen 62
+        assert!(voice_playing(&synth, 74).is_some());
+    }
+
+
+    #[test]
+    fn release_all_lets_go_of_held_and_stolen_notes() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+
+        synth.note_on(60, 100.0);
+        synth.note_on(62, 100.0);
+        render(&mut synth, &params, 2);
+        synth.note_on(64, 100.0);
+
+        synth.release_all();
+        render(&mut synth, &params, 200);
+
+        assert!(synth.voices.iter().all(|voice| voice.note == 0));
+        assert!(synth.held_notes.is_empty());
+    }
+
+
+    #[test]
+    fn paraphonic_slots_stop_with_the_shared_envelope() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+        synth.env_release = 0.9;
+        synth.set_poly_mode(crate::PolyMode::Paraphonic);
+
+        synth.note_on(60, 100.0);
+        synth.note_on(64, 100.0);
+        render(&mut synth, &params, 10);
+        assert!(synth.voices[0].envelope.is_active());
+        assert!(synth.voices[1..].iter().all(|voice| !voice.envelope.is_active()));
+
+        synth.note_off(60);
+        synth.note_off(64);
+        render(&mut synth, &params, 1000);
+
+        assert!(synth
+            .voices
+            .iter()
+            .all(|voice| !voice.envelope.is_active() && !voice.gated));
+    }
+
+    #[test]
+    fn new_paraphonic_phrase_fades_out_the_ringing_slots() {
+        let params = RX11Params::default();
+        let mut synth = test_synth();
+        synth.num_voices = 4;
+        synth.env_release = 0.999;
+        synth.set_poly_mode(crate::PolyMode::Paraphonic);
+
+        synth.note_on(60, 100.0);
+        render(&mut synth, &params, 10);
+        synth.note_off(60);
+        render(&mut synth, &params, 10);
+        let level = synth.voices[0].envelope.level;
+
+        // The slot playing 60 keeps ringing under the new note until its fade is done
+        synth.note_on(64, 100.0);
+        assert!(synth.voices[1].gated && synth.voices[2].gated);
+        assert!(synth.voices[0].envelope.level >= level);
+
+        render(&mut synth, &params, 200);
+        assert!(!synth.voices[1].gated);
+        assert!(synth.voices[2].gated && synth.voices[2].note == 64);
+    }
+
+    #[test]
+    fn duo_notes_glide_in_constant_time() {
+        let mut synth = test_synth();
+        synth.set_poly_mode(crate::PolyMode::Duo);
+        synth.glide_type = crate::GlideType::ConstantTime;
+        synth.glide_updates = 10.0;
+
+        synth.note_on(60, 100.0);
+        synth.note_on(62, 100.0);
+        synth.glide_mode = crate::GlideMode::Legato;
+
+        // Oscillator 1 glides down an octave, then the duo note glides up by a different interval
+        synth.note_on(48, 100.0);
+        synth.note_on(72, 100.0);
+
+        let voice = &mut synth.voices[0];
+        let steps_left = |voice: &Voice| {
+            (voice.duo_target_period / voice.duo_period).ln().abs() / voice.duo_glide_ratio.ln()
+        };
+        for _ in 0..9 {
+            voice.update_lfo();
+        }
+        assert!((steps_left(voice) - 1.0).abs() < 0.01);
+
+        voice.update_lfo();
+        assert!(steps_left(voice) < 0.01);
+    }
+}
@@ -0,0 +1,392 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Every tuning gets its own generation so the synth can tell when it has to update its table.
+// 0 is the default 12-TET tuning.
+static NEXT_GENERATION: AtomicU32 = AtomicU32::new(1);
+
+fn next_generation() -> u32 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A microtuning loaded from a Scala scale and an optional keyboard mapping
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tuning {
+    pub name: String,
+    pub scale_size: usize,
+    /// The pitch of every MIDI note in (fractional) MIDI note numbers, `None` for unmapped keys
+    pub pitches: Vec<Option<f32>>,
+    #[serde(skip, default = "next_generation")]
+    pub generation: u32,
+}
+
+/// The MIDI note pitches of standard 12-TET tuning
+pub fn equal_temperament() -> [f32; 128] {
+    std::array::from_fn(|note| note as f32)
+}
+
+struct KeyboardMapping {
+    first_note: i32,
+    last_note: i32,
+    middle_note: i32,
+    reference_note: i32,
+    reference_frequency: f32,
+    octave_degree: i32,
+    /// Scale degree of every key in the mapping, `None` for keys that don't play
+    map: Vec<Option<i32>>,
+}
+
+// Scala files use '!' for comments, everything else is data
+fn data_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.starts_with('!'))
+}
+
+// A pitch is in cents when it has a period, otherwise it's a ratio like 3/2 or 2
+fn parse_pitch(line: &str) -> Result<f32, String> {
+    let value = line.split_whitespace().next().unwrap_or("");
+
+    if value.contains('.') {
+        return value
+            .parse::<f32>()
+            .map_err(|_| format!("Invalid pitch in cents: {}", value));
+    }
+
+    let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+    match (numerator.parse::<f32>(), denominator.parse::<f32>()) {
+        (Ok(numerator), Ok(denominator)) if numerator > 0.0 && denominator > 0.0 => {
+            Ok(1200.0 * (numerator / denominator).log2())
+        }
+        _ => Err(format!("Invalid pitch ratio: {}", value)),
+    }
+}
+
+// Returns the description and the cents of every degree, the last one is the period of the scale
+fn parse_scl(scl: &str) -> Result<(String, Vec<f32>), String> {
+    let mut lines = data_lines(scl);
+    let description = lines.next().ok_or("The scale file is empty")?.to_string();
+
+    let count = lines
+        .find(|line| !line.is_empty())
+        .and_then(|line| line.split_whitespace().next())
+        .and_then(|count| count.parse::<usize>().ok())
+        .ok_or("The scale file has no note count")?;
+
+    let pitches = lines
+        .filter(|line| !line.is_empty())
+        .take(count)
+        .map(parse_pitch)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if count == 0 || pitches.len() != count {
+        return Err(format!(
+            "The scale should have {} notes but has {}",
+            count,
+            pitches.len()
+        ));
+    }
+
+    Ok((description, pitches))
+}
+
+fn parse_kbm(kbm: &str, scale_size: usize) -> Result<KeyboardMapping, String> {
+    let mut lines = data_lines(kbm).filter(|line| !line.is_empty());
+    let mut next_value = |name: &str| {
+        lines
+            .next()
+            .and_then(|line| line.split_whitespace().next())
+            .map(|value| value.to_string())
+            .ok_or_else(|| format!("The keyboard mapping has no {}", name))
+    };
+    let parse_int = |value: String| {
+        value
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid number in the keyboard mapping: {}", value))
+    };
+
+    let map_size = parse_int(next_value("map size")?)?;
+    // One entry per key at most, a bigger size would fill the map with unmapped keys
+    if !(0..=128).contains(&map_size) {
+        return Err(format!("Invalid keyboard mapping size: {}", map_size));
+    }
+
+    let first_note = parse_int(next_value("first note")?)?;
+    let last_note = parse_int(next_value("last note")?)?;
+    let middle_note = parse_int(next_value("middle note")?)?;
+    let reference_note = parse_int(next_value("reference note")?)?;
+    let reference_frequency = next_value("reference frequency")?
+        .parse::<f32>()
+        .map_err(|_| "Invalid reference frequency in the keyboard mapping".to_string())?;
+    let mut octave_degree = parse_int(next_value("octave degree")?)?;
+
+    let mut map = Vec::new();
+    for _ in 0..map_size {
+        // Missing entries at the end of the file are unmapped
+        match next_value("mapping") {
+            Ok(value) if value == "x" => map.push(None),
+            Ok(value) => map.push(Some(parse_int(value)?)),
+            Err(_) => map.push(None),
+        }
+    }
+
+    if octave_degree <= 0 {
+        octave_degree = scale_size as i32;
+    }
+
+    Ok(KeyboardMapping {
+        first_note,
+        last_note,
+        middle_note,
+        reference_note,
+        reference_frequency,
+        octave_degree,
+        map,
+    })
+}
+
+impl KeyboardMapping {
+    // Without a .kbm file the scale starts at middle C and A4 is 440 Hz
+    fn linear(scale_size: usize) -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 69,
+            reference_frequency: 440.0,
+            octave_degree: scale_size as i32,
+            map: Vec::new(),
+        }
+    }
+
+    fn degree(&self, note: i32) -> Option<i32> {
+        let offset = note - self.middle_note;
+
+        if self.map.is_empty() {
+            return Some(offset);
+        }
+
+        let map_size = self.map.len() as i32;
+        let octave = offset.div_euclid(map_size);
+        let degree = self.map[offset.rem_euclid(map_size) as usize]?;
+
+        Some(octave * self.octave_degree + degree)
+    }
+}
+
+// The cents of a scale degree, counting from the middle note
+fn degree_cents(cents: &[f32], degree: i32) -> f32 {
+    let scale_size = cents.len() as i32;
+    let period = cents[cents.len() - 1];
+    let octave = degree.div_euclid(scale_size);
+    let step = degree.rem_euclid(scale_size) as usize;
+
+    let step_cents = if step == 0 { 0.0 } else { cents[step - 1] };
+    octave as f32 * period + step_cents
+}
+
+impl Tuning {
+    pub fn from_scala(scl: &str, kbm: Option<&str>) -> Result<Self, String> {
+        let (description, cents) = parse_scl(scl)?;
+        let mapping = match kbm {
+            Some(kbm) => parse_kbm(kbm, cents.len())?,
+            None => KeyboardMapping::linear(cents.len()),
+        };
+
+        // The reference note sounds at the reference frequency even if it isn't mapped
+        let reference_cents = degree_cents(
+            &cents,
+            mapping
+                .degree(mapping.reference_note)
+                .unwrap_or(mapping.reference_note - mapping.middle_note),
+        );
+        let reference_pitch = 69.0 + 12.0 * (mapping.reference_frequency / 440.0).log2();
+
+        let pitches = (0..128)
+            .map(|note| {
+                if note < mapping.first_note || note > mapping.last_note {
+                    return None;
+                }
+
+                let degree = mapping.degree(note)?;
+                Some(reference_pitch + (degree_cents(&cents, degree) - reference_cents) / 100.0)
+            })
+            .collect();
+
+        Ok(Self {
+            name: description,
+            scale_size: cents.len(),
+            pitches,
+            generation: next_generation(),
+        })
+    }
+
+    /// The pitches as the synth's tuning table, unmapped keys are NaN
+    pub fn table(&self) -> [f32; 128] {
+        std::array::from_fn(|note| {
+            self.pitches
+                .get(note)
+                .copied()
+                .flatten()
+                .unwrap_or(f32::NAN)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWELVE_TET: &str = "! 12-TET.scl
+!
+12 tone equal temperament
+ 12
+!
+ 100.0
+ 200.0
+ 300.0
+ 400.0
+ 500.0
+ 600.0
+ 700.0
+ 800.0
+ 900.0
+ 1000.0
+ 1100.0
+ 2/1
+";
+
+    fn assert_close(left: f32, right: f32) {
+        assert!((left - right).abs() < 0.001, "{} != {}", left, right);
+    }
+
+    #[test]
+    fn pitches_are_cents_or_ratios() {
+        assert_close(parse_pitch("701.955").unwrap(), 701.955);
+        assert_close(parse_pitch("-50.").unwrap(), -50.0);
+        assert_close(parse_pitch("3/2").unwrap(), 701.955);
+        assert_close(parse_pitch("2").unwrap(), 1200.0);
+        // Anything after the value is a comment
+        assert_close(parse_pitch("5/4 major third").unwrap(), 386.3137);
+
+        assert!(parse_pitch("0/1").is_err());
+        assert!(parse_pitch("3/0").is_err());
+        assert!(parse_pitch("fifth").is_err());
+        assert!(parse_pitch("1.2.3").is_err());
+    }
+
+    #[test]
+    fn parses_scales_around_comments() {
+        let (description, cents) = parse_scl(TWELVE_TET).unwrap();
+        assert_eq!(description, "12 tone equal temperament");
+        assert_eq!(cents.len(), 12);
+        assert_close(cents[0], 100.0);
+        assert_close(cents[11], 1200.0);
+
+        // The description can be empty
+        let (description, cents) = parse_scl("!\n\n2\n3/2\n2/1\n").unwrap();
+        assert_eq!(description, "");
+        assert_eq!(cents.len(), 2);
+    }
+
+    #[test]
+    fn rejects_broken_scales() {
+        assert!(parse_scl("").is_err());
+        assert!(parse_scl("No count\n").is_err());
+        assert!(parse_scl("Too few\n3\n100.0\n2/1\n").is_err());
+        assert!(parse_scl("Empty\n0\n").is_err());
+        assert!(parse_scl("Bad pitch\n1\nsomething\n").is_err());
+    }
+
+    #[test]
+    fn parses_keyboard_mappings() {
+        let kbm = "! White keys only
+7
+0
+127
+60
+69
+440.0
+12
+! The mapping
+0
+2
+4
+5
+7
+x
+";
+        let mapping = parse_kbm(kbm, 12).unwrap();
+        assert_eq!(mapping.octave_degree, 12);
+        // An x and the missing last entry are both unmapped
+        assert_eq!(mapping.map, [Some(0), Some(2), Some(4), Some(5), Some(7), None, None]);
+
+        assert_eq!(mapping.degree(60), Some(0));
+        assert_eq!(mapping.degree(64), Some(7));
+        assert_eq!(mapping.degree(65), None);
+        assert_eq!(mapping.degree(67), Some(12));
+        assert_eq!(mapping.degree(59), None);
+        assert_eq!(mapping.degree(57), Some(-5));
+    }
+
+    #[test]
+    fn empty_keyboard_mappings_are_linear() {
+        let kbm = "0\n0\n127\n60\n69\n440.0\n0\n";
+        let mapping = parse_kbm(kbm, 7).unwrap();
+
+        assert!(mapping.map.is_empty());
+        // The octave degree falls back to the scale size
+        assert_eq!(mapping.octave_degree, 7);
+        assert_eq!(mapping.degree(50), Some(-10));
+
+        assert!(parse_kbm("0\n0\n127\n", 7).is_err());
+        assert!(parse_kbm("a\n0\n127\n60\n69\n440.0\n0\n", 7).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_keyboard_mappings() {
+        assert!(parse_kbm("128\n0\n127\n60\n69\n440.0\n0\n", 7).is_ok());
+        assert!(parse_kbm("129\n0\n127\n60\n69\n440.0\n0\n", 7).is_err());
+        assert!(parse_kbm("2000000000\n0\n127\n60\n69\n440.0\n0\n", 7).is_err());
+        assert!(parse_kbm("-1\n0\n127\n60\n69\n440.0\n0\n", 7).is_err());
+    }
+
+    #[test]
+    fn twelve_tet_matches_equal_temperament() {
+        let tuning = Tuning::from_scala(TWELVE_TET, None).unwrap();
+        assert_eq!(tuning.scale_size, 12);
+
+        for (pitch, note) in tuning.table().iter().zip(equal_temperament()) {
+            assert_close(*pitch, note);
+        }
+    }
+
+    #[test]
+    fn keyboard_mappings_move_the_reference_and_leave_keys_out() {
+        // A4 at 432 Hz, keys outside 48..72 and the black keys don't play
+        let kbm = "12\n48\n72\n60\n69\n432.0\n12\n0\nx\n2\nx\n4\n5\nx\n7\nx\n9\nx\n11\n";
+        let tuning = Tuning::from_scala(TWELVE_TET, Some(kbm)).unwrap();
+        let table = tuning.table();
+        let offset = 12.0 * (432.0_f32 / 440.0).log2();
+
+        assert_close(table[69], 69.0 + offset);
+        assert_close(table[60], 60.0 + offset);
+        assert!(table[61].is_nan());
+        assert!(table[47].is_nan());
+        assert!(table[74].is_nan());
+        assert_close(table[72], 72.0 + offset);
+    }
+
+    #[test]
+    fn scales_repeat_at_their_period() {
+        // A 3 note scale that repeats at the fifth, mapped straight onto the keys
+        let tuning = Tuning::from_scala("Fifths\n3\n200.0\n500.0\n3/2\n", None).unwrap();
+        let table = tuning.table();
+
+        // Middle C is the first degree, A4 stays at 440 Hz
+        assert_close(table[69], 69.0);
+        assert_close(table[63] - table[60], 7.01955);
+        assert_close(table[61] - table[60], 2.0);
+        assert_close(table[62] - table[60], 5.0);
+    }
+}
@@ -4,9 +4,28 @@ use nih_plug_egui::{resizable_window::ResizableWindow};
 use nih_plug_egui::EguiState;
 use crate::egui::{Context, Vec2};
 use crate::rotary_slider::{Knob, KnobStyle, LabelPosition};
+use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 
-use crate::{EventCollector, GlideMode, PolyMode, Preset, Presets, RX11Params, UiState};
+use nih_plug::prelude::{Enum, EnumParam, Param, ParamPtr};
+use parking_lot::RwLock;
+
+use crate::chord_memory::ChordLearn;
+use crate::midi_map::{MidiLearn, MidiMapping};
+use crate::presets::PresetTarget;
+use crate::sequencer::Sequence;
+use crate::tuning::Tuning;
+use crate::{
+    ArpMode, ChorusMode, DelayMode, EventCollector, FxSlotParams, FxType, GlideMode, GlideType, MpeZone, NoteDivision, NotePriority, PanSpreadMode, PolyMode, Preset, Presets, RX11Params, UiState,
+    VoiceStealMode,
+};
+
+// What the controls need to offer MIDI learn from their context menus
+struct MidiContext<'a> {
+    learn: &'a MidiLearn,
+    map: &'a RwLock<Vec<MidiMapping>>,
+    param_ids: &'a [(String, ParamPtr)],
+}
 
 #[derive(Clone)]
 pub enum UiView {
@@ -22,14 +41,48 @@ pub fn rx11_egui_ui(
     params: &RX11Params,
     presets: &Presets,
     logger: &EventCollector,
+    midi_learn: &MidiLearn,
+    chord_learn: &ChordLearn,
+    param_ids: &[(String, ParamPtr)],
+    program_change: &AtomicI32,
 ) {
     let UiState { 
         selected_preset, 
         loaded_preset_on_startup, 
         _current_view, 
-        show_debug
+        show_debug,
+        show_midi_map,
+        show_tuning,
+        show_sequencer,
+        show_fx_rack,
+        scale_path,
+        keyboard_map_path,
+        tuning_error,
     } = state;
 
+    let midi = MidiContext {
+        learn: midi_learn,
+        map: &params.midi_map,
+        param_ids,
+    };
+
+    // The audio thread has already queued the program change's values, this only shows the preset
+    let program = program_change.swap(-1, Ordering::Relaxed);
+    if let Some(preset) = usize::try_from(program).ok().and_then(|program| presets.0.get(program)) {
+        *selected_preset = preset.name.clone();
+    }
+
+    // The audio thread reports the CC, binding it happens here so it never allocates
+    if let Some((param_id, cc)) = midi_learn.take_learned() {
+        let mut midi_map = params.midi_map.write();
+        midi_map.retain(|mapping| mapping.param_id != param_id);
+        midi_map.push(MidiMapping::new(cc, param_id));
+    }
+
+    if let Some(intervals) = chord_learn.take_learned() {
+        *params.chord.write() = intervals;
+    }
+
     ResizableWindow::new("res-wind")
         .min_size(Vec2::new(800.0, 600.0))
         .show(egui_ctx, egui_state.as_ref(), |_ui| { 
@@ -39,7 +92,7 @@ pub fn rx11_egui_ui(
             // place to load the state of whatever the user last used
             if !*loaded_preset_on_startup {
                 if let Some(preset) = presets.0.first() {
-                    load_preset(&preset, &setter, &params);
+                    load_preset(&preset, setter, &params);
                     *loaded_preset_on_startup = true;
                     tracing::debug!("The preset was loaded on startup.");
                 } else {
@@ -59,7 +112,7 @@ pub fn rx11_egui_ui(
                                 for preset in &presets.0 {
                                     if ui.button(&preset.name).clicked() {
                                         *selected_preset = preset.name.clone();
-                                        load_preset(&preset, &setter, &params);
+                                        load_preset(&preset, setter, &params);
                                         // How do I close the dropdown on button click?
                                     }
                                 }
@@ -67,6 +120,27 @@ pub fn rx11_egui_ui(
                     });
                     ui.label(format!("Preset: {}", selected_preset));
 
+                    if ui.add(egui::Button::new("MIDI Map")).clicked() {
+                        *show_midi_map = !*show_midi_map;
+                    }
+
+                    if ui.add(egui::Button::new("Tuning")).clicked() {
+                        *show_tuning = !*show_tuning;
+                    }
+
+                    if ui.add(egui::Button::new("Sequencer")).clicked() {
+                        *show_sequencer = !*show_sequencer;
+                    }
+
+                    if ui.add(egui::Button::new("FX Rack")).clicked() {
+                        *show_fx_rack = !*show_fx_rack;
+                    }
+
+                    if midi_learn.target.lock().is_some() {
+                        ui.label("MIDI Learn: move a control on your MIDI device");
+                        egui_ctx.request_repaint();
+                    }
+
                     if *show_debug {
                         if ui.add(egui::Button::new("Debug Hide")).clicked() {
                             *show_debug = false;
@@ -79,11 +153,27 @@ pub fn rx11_egui_ui(
                 })
             }); // MENU END
 
-            synth_view(egui_ctx, setter, params);
+            synth_view(egui_ctx, setter, params, &midi, chord_learn);
 
             if *show_debug {
                 debug_view(egui_ctx, logger);
             }
+
+            if *show_midi_map {
+                midi_map_view(egui_ctx, &midi);
+            }
+
+            if *show_tuning {
+                tuning_view(egui_ctx, params, scale_path, keyboard_map_path, tuning_error);
+            }
+
+            if *show_sequencer {
+                sequencer_view(egui_ctx, params);
+            }
+
+            if *show_fx_rack {
+                fx_rack_view(egui_ctx, setter, params);
+            }
         
     });
 }
@@ -111,10 +201,329 @@ fn debug_view(
     });
 }
 
+fn midi_map_view(egui_ctx: &Context, midi: &MidiContext) {
+    egui::Window::new("MIDI Map").min_width(400.0).show(egui_ctx, |ui| {
+        // Edit a copy so the audio thread is never locked out for longer than the write below
+        let mut midi_map = midi.map.read().clone();
+        let mut remove = None;
+
+        if midi_map.is_empty() {
+            ui.label("Right click a control and choose MIDI Learn to map a CC to it");
+        }
+
+        egui::Grid::new("midi-map").striped(true).show(ui, |ui| {
+            ui.label("CC");
+            ui.label("Parameter");
+            ui.label("Min");
+            ui.label("Max");
+            ui.label("Invert");
+            ui.end_row();
+
+            for (idx, mapping) in midi_map.iter_mut().enumerate() {
+                ui.add(egui::DragValue::new(&mut mapping.cc).range(0..=127));
+                ui.label(param_name(midi.param_ids, &mapping.param_id));
+                ui.add(egui::Slider::new(&mut mapping.min, 0.0..=1.0));
+                ui.add(egui::Slider::new(&mut mapping.max, 0.0..=1.0));
+                ui.checkbox(&mut mapping.invert, "");
+                if ui.button("Remove").clicked() {
+                    remove = Some(idx);
+                }
+                ui.end_row();
+            }
+        });
+
+        if let Some(idx) = remove {
+            midi_map.remove(idx);
+        }
+
+        if !midi_map.is_empty() && ui.button("Clear All").clicked() {
+            midi_map.clear();
+        }
+
+        if *midi.map.read() != midi_map {
+            *midi.map.write() = midi_map;
+        }
+    });
+}
+
+fn tuning_view(
+    egui_ctx: &Context,
+    params: &RX11Params,
+    scale_path: &mut String,
+    keyboard_map_path: &mut String,
+    tuning_error: &mut Option<String>,
+) {
+    egui::Window::new("Tuning").min_width(400.0).show(egui_ctx, |ui| {
+        match params.scala_tuning.read().as_ref() {
+            Some(tuning) => ui.label(format!("{} ({} notes)", tuning.name, tuning.scale_size)),
+            None => ui.label("12-TET"),
+        };
+        ui.separator();
+
+        egui::Grid::new("tuning").show(ui, |ui| {
+            ui.label("Scale (.scl)");
+            ui.text_edit_singleline(scale_path);
+            ui.end_row();
+
+            ui.label("Keyboard Mapping (.kbm)");
+            ui.text_edit_singleline(keyboard_map_path);
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Load").clicked() {
+                match load_tuning(scale_path, keyboard_map_path) {
+                    Ok(tuning) => {
+                        *params.scala_tuning.write() = Some(tuning);
+                        *tuning_error = None;
+                    }
+                    Err(err) => *tuning_error = Some(err),
+                }
+            }
+
+            if ui.button("Clear").clicked() {
+                *params.scala_tuning.write() = None;
+                *tuning_error = None;
+            }
+        });
+
+        if let Some(err) = tuning_error {
+            ui.colored_label(egui::Color32::RED, err.as_str());
+        }
+    });
+}
+
+fn sequencer_view(egui_ctx: &Context, params: &RX11Params) {
+    egui::Window::new("Sequencer").min_width(400.0).show(egui_ctx, |ui| {
+        ui.label("Plays while the host does, or while a key is held. Keys transpose it from C4.");
+        ui.separator();
+
+        // Edit a copy so the audio thread is never locked out for longer than the write below
+        let mut sequence = params.sequence.read().clone();
+        let length = (params.seq_steps.value() as usize).min(sequence.steps.len());
+
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            egui::Grid::new("sequencer").striped(true).show(ui, |ui| {
+                ui.label("Step");
+                for idx in 0..length {
+                    ui.label(format!("{}", idx + 1));
+                }
+                ui.end_row();
+
+                ui.label("On");
+                for step in &mut sequence.steps[..length] {
+                    ui.checkbox(&mut step.on, "");
+                }
+                ui.end_row();
+
+                ui.label("Note");
+                for step in &mut sequence.steps[..length] {
+                    ui.add(
+                        egui::DragValue::new(&mut step.note)
+                            .range(0..=127)
+                            .custom_formatter(|note, _| note_name(note as i32)),
+                    );
+                }
+                ui.end_row();
+
+                ui.label("Velocity");
+                for step in &mut sequence.steps[..length] {
+                    ui.add(egui::DragValue::new(&mut step.velocity).range(1.0..=127.0).speed(1.0));
+                }
+                ui.end_row();
+
+                ui.label("Gate");
+                for step in &mut sequence.steps[..length] {
+                    ui.add(egui::DragValue::new(&mut step.gate).range(0.01..=1.0).speed(0.01));
+                }
+                ui.end_row();
+
+                ui.label("Slide");
+                for step in &mut sequence.steps[..length] {
+                    ui.checkbox(&mut step.slide, "");
+                }
+                ui.end_row();
+
+                ui.label("Accent");
+                for step in &mut sequence.steps[..length] {
+                    ui.checkbox(&mut step.accent, "");
+                }
+                ui.end_row();
+            });
+        });
+
+        if ui.button("Clear").clicked() {
+            sequence = Sequence::default();
+        }
+
+        if *params.sequence.read() != sequence {
+            *params.sequence.write() = sequence;
+        }
+    });
+}
+
+const FX_TYPES: [(FxType, &str); 7] = [
+    (FxType::None, "None"),
+    (FxType::Distortion, "Distortion"),
+    (FxType::Chorus, "Chorus"),
+    (FxType::Phaser, "Phaser"),
+    (FxType::Delay, "Delay"),
+    (FxType::Reverb, "Reverb"),
+    (FxType::Equalizer, "EQ"),
+];
+
+fn fx_rack_view(egui_ctx: &Context, mut setter: &ParamSetter, params: &RX11Params) {
+    egui::Window::new("FX Rack").min_width(300.0).show(egui_ctx, |ui| {
+        ui.label("Drag a slot by its number to change the order the effects run in");
+        ui.separator();
+
+        let mut moved = None;
+
+        for (idx, slot) in params.fx_slots.iter().enumerate() {
+            let (_, dropped) = ui.dnd_drop_zone::<usize, ()>(egui::Frame::default(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.dnd_drag_source(egui::Id::new(("fx-slot", idx)), idx, |ui| {
+                        ui.label(format!("☰ {}", idx + 1));
+                    });
+
+                    let current = slot.fx_type.value();
+                    let current_name = FX_TYPES
+                        .iter()
+                        .find(|(fx_type, _)| *fx_type == current)
+                        .map_or("None", |(_, name)| *name);
+
+                    egui::ComboBox::from_id_salt(("fx-type", idx))
+                        .selected_text(current_name)
+                        .show_ui(ui, |ui| {
+                            for (fx_type, name) in FX_TYPES {
+                                if ui.selectable_label(current == fx_type, name).clicked() {
+                                    // There is only one of each effect, it moves out of the slot
+                                    // it was in
+                                    for other in &params.fx_slots {
+                                        if fx_type != FxType::None && other.fx_type.value() == fx_type {
+                                            setter.set_param(&other.fx_type, FxType::None);
+                                        }
+                                    }
+                                    setter.set_param(&slot.fx_type, fx_type);
+                                }
+                            }
+                        });
+
+                    let mut bypass = slot.bypass.value();
+                    if ui.checkbox(&mut bypass, "Bypass").changed() {
+                        setter.set_param(&slot.bypass, bypass);
+                    }
+                });
+            });
+
+            if let Some(from) = dropped {
+                moved = Some((*from, idx));
+            }
+        }
+
+        if let Some((from, to)) = moved {
+            let mut slots: Vec<(FxType, bool)> = params
+                .fx_slots
+                .iter()
+                .map(|slot| (slot.fx_type.value(), slot.bypass.value()))
+                .collect();
+            let slot = slots.remove(from);
+            slots.insert(to, slot);
+
+            for (slot_params, (fx_type, bypass)) in params.fx_slots.iter().zip(slots) {
+                setter.set_param(&slot_params.fx_type, fx_type);
+                setter.set_param(&slot_params.bypass, bypass);
+            }
+        }
+    });
+}
+
+// Middle C, note 60, is C4
+fn note_name(note: i32) -> String {
+    const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    format!("{}{}", NAMES[note.rem_euclid(12) as usize], note.div_euclid(12) - 1)
+}
+
+// The keyboard mapping is optional, without one the scale starts at middle C
+fn load_tuning(scale_path: &str, keyboard_map_path: &str) -> Result<Tuning, String> {
+    // Scala files are often Latin-1 rather than UTF-8, only the description can be affected
+    let read = |path: &str| {
+        std::fs::read(path.trim())
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .map_err(|err| format!("Failed to read {}: {}", path, err))
+    };
+
+    let scl = read(scale_path)?;
+    let kbm = match keyboard_map_path.trim() {
+        "" => None,
+        path => Some(read(path)?),
+    };
+
+    let mut tuning = Tuning::from_scala(&scl, kbm.as_deref())?;
+    if tuning.name.is_empty() {
+        if let Some(file_name) = std::path::Path::new(scale_path.trim()).file_stem() {
+            tuning.name = file_name.to_string_lossy().into_owned();
+        }
+    }
+
+    Ok(tuning)
+}
+
+fn param_name(param_ids: &[(String, ParamPtr)], param_id: &str) -> String {
+    match param_ids.iter().find(|(id, _)| id == param_id) {
+        // SAFETY: The pointers come from the plugin's parameters, which outlive the editor
+        Some((_, param)) => unsafe { param.name() }.to_string(),
+        None => param_id.to_string(),
+    }
+}
+
+// Right click menu for binding a control to a MIDI CC
+fn midi_learn_menu<P: Param>(ui: &mut egui::Ui, midi: &MidiContext, param: &P) {
+    let ptr = param.as_ptr();
+    let Some((param_id, _)) = midi.param_ids.iter().find(|(_, p)| *p == ptr) else {
+        return;
+    };
+
+    let mapped_cc = midi
+        .map
+        .read()
+        .iter()
+        .find(|mapping| mapping.param_id == *param_id)
+        .map(|mapping| mapping.cc);
+    if let Some(cc) = mapped_cc {
+        ui.label(format!("Mapped to CC {}", cc));
+    }
+
+    if midi.learn.is_learning(param_id) {
+        if ui.button("Cancel MIDI Learn").clicked() {
+            midi.learn.cancel();
+            ui.close_menu();
+        }
+    } else if ui.button("MIDI Learn").clicked() {
+        midi.learn.arm(param_id);
+        ui.close_menu();
+    }
+
+    if mapped_cc.is_some() && ui.button("Clear MIDI Mapping").clicked() {
+        midi.map
+            .write()
+            .retain(|mapping| mapping.param_id != *param_id);
+        ui.close_menu();
+    }
+}
+
+// A parameter slider that can be MIDI learned
+fn param_slider<P: Param>(ui: &mut egui::Ui, setter: &ParamSetter, midi: &MidiContext, param: &P) {
+    ui.add(widgets::ParamSlider::for_param(param, setter))
+        .context_menu(|ui| midi_learn_menu(ui, midi, param));
+}
+
 fn synth_view(
     egui_ctx: &Context,
     setter: &ParamSetter,
-    params: &RX11Params
+    params: &RX11Params,
+    midi: &MidiContext,
+    chord_learn: &ChordLearn,
 ) {
     egui::CentralPanel::default().show(egui_ctx, |ui| {
         // TODO:
@@ -125,10 +534,12 @@ fn synth_view(
         let mut vol_raw2 = vol_raw.clone(); // 
         let vol_min = nih_plug::util::db_to_gain(-30.0);
         let vol_max = nih_plug::util::db_to_gain(6.0);
-        if ui.add(
+        let vol_knob = ui.add(
                 Knob::new(&mut vol_raw2, vol_min, vol_max, KnobStyle::Wiper)
                     .with_label("Volume", LabelPosition::Bottom)
-            ).dragged() {
+            );
+        vol_knob.context_menu(|ui| midi_learn_menu(ui, midi, &params.output_level));
+        if vol_knob.dragged() {
             setter.begin_set_parameter(&params.output_level);
             setter.set_parameter(&params.output_level, vol_raw2);
             setter.end_set_parameter(&params.output_level);
@@ -205,97 +616,438 @@ fn synth_view(
                         setter.set_parameter(&params.poly_mode, PolyMode::Poly);
                         setter.end_set_parameter(&params.poly_mode);
                     }
+                    if ui
+                        .add(egui::widgets::SelectableLabel::new(
+                            *poly_mode == PolyMode::Duo,
+                            "Duo",
+                        ))
+                        .clicked()
+                    {
+                        setter.begin_set_parameter(&params.poly_mode);
+                        setter.set_parameter(&params.poly_mode, PolyMode::Duo);
+                        setter.end_set_parameter(&params.poly_mode);
+                    }
+                    if ui
+                        .add(egui::widgets::SelectableLabel::new(
+                            *poly_mode == PolyMode::Paraphonic,
+                            "Paraphonic",
+                        ))
+                        .clicked()
+                    {
+                        setter.begin_set_parameter(&params.poly_mode);
+                        setter.set_parameter(&params.poly_mode, PolyMode::Paraphonic);
+                        setter.end_set_parameter(&params.poly_mode);
+                    }
+                });
+                ui.end_row();
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Note Priority");
+                    enum_selector(
+                        ui,
+                        setter,
+                        midi,
+                        &params.note_priority,
+                        &[
+                            (NotePriority::Last, "Last"),
+                            (NotePriority::Lowest, "Lowest"),
+                            (NotePriority::Highest, "Highest"),
+                        ],
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Mono Retrigger");
+                param_slider(ui, setter, midi, &params.mono_retrigger);
+
+                ui.separator();
+
+                ui.label("Polyphony");
+                param_slider(ui, setter, midi, &params.polyphony);
+
+                ui.horizontal(|ui| {
+                    ui.label("Voice Steal");
+                    enum_selector(
+                        ui,
+                        setter,
+                        midi,
+                        &params.voice_steal,
+                        &[
+                            (VoiceStealMode::Quietest, "Quietest"),
+                            (VoiceStealMode::Oldest, "Oldest"),
+                            (VoiceStealMode::Lowest, "Lowest"),
+                            (VoiceStealMode::Highest, "Highest"),
+                            (VoiceStealMode::SameNote, "Same Note"),
+                            (VoiceStealMode::Released, "Released"),
+                        ],
+                    );
                 });
                 ui.end_row();
 
                 ui.separator();
 
                 ui.label("Oscillator Mix");
-                ui.add(widgets::ParamSlider::for_param(&params.osc_mix, setter));
+                param_slider(ui, setter, midi, &params.osc_mix);
 
                 ui.label("Oscillator Tune");
-                ui.add(widgets::ParamSlider::for_param(&params.osc_tune, setter));
+                param_slider(ui, setter, midi, &params.osc_tune);
 
                 ui.label("Oscillator Fine Tune");
-                ui.add(widgets::ParamSlider::for_param(
-                    &params.osc_fine_tune,
-                    setter,
-                ));
+                param_slider(ui, setter, midi, &params.osc_fine_tune);
 
                 ui.label("Glide Rate");
-                ui.add(widgets::ParamSlider::for_param(&params.glide_rate, setter));
+                param_slider(ui, setter, midi, &params.glide_rate);
 
                 ui.label("Glide Bend");
-                ui.add(widgets::ParamSlider::for_param(&params.glide_bend, setter));
+                param_slider(ui, setter, midi, &params.glide_bend);
+
+                ui.horizontal(|ui| {
+                    ui.label("Glide Type");
+                    enum_selector(
+                        ui,
+                        setter,
+                        midi,
+                        &params.glide_type,
+                        &[
+                            (GlideType::Exponential, "Exponential"),
+                            (GlideType::ConstantTime, "Constant Time"),
+                            (GlideType::ConstantRate, "Constant Rate"),
+                        ],
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Glide Time");
+                param_slider(ui, setter, midi, &params.glide_time);
+
+                ui.label("Glide Sync");
+                param_slider(ui, setter, midi, &params.glide_sync);
+
+                ui.label("Glide Division");
+                param_slider(ui, setter, midi, &params.glide_division);
 
                 ui.label("Filter Frequency");
-                ui.add(widgets::ParamSlider::for_param(&params.filter_freq, setter));
+                param_slider(ui, setter, midi, &params.filter_freq);
 
                 ui.label("Filter Resonance");
-                ui.add(widgets::ParamSlider::for_param(&params.filter_reso, setter));
+                param_slider(ui, setter, midi, &params.filter_reso);
 
                 ui.label("Filter LFO");
-                ui.add(widgets::ParamSlider::for_param(&params.filter_lfo, setter));
+                param_slider(ui, setter, midi, &params.filter_lfo);
 
                 ui.label("Filter Velocity");
-                ui.add(widgets::ParamSlider::for_param(
-                    &params.filter_velocity,
-                    setter,
-                ));
+                param_slider(ui, setter, midi, &params.filter_velocity);
 
                 ui.label("Filter ADSR");
-                ui.add(widgets::ParamSlider::for_param(&params.filter_env, setter));
+                param_slider(ui, setter, midi, &params.filter_env);
 
                 ui.label("Filter Attack");
-                ui.add(widgets::ParamSlider::for_param(
-                    &params.filter_attack,
-                    setter,
-                ));
+                param_slider(ui, setter, midi, &params.filter_attack);
 
                 ui.label("Filter Decay");
-                ui.add(widgets::ParamSlider::for_param(
-                    &params.filter_decay,
-                    setter,
-                ));
+                param_slider(ui, setter, midi, &params.filter_decay);
 
                 ui.label("Filter Sustain");
-                ui.add(widgets::ParamSlider::for_param(
-                    &params.filter_sustain,
-                    setter,
-                ));
+                param_slider(ui, setter, midi, &params.filter_sustain);
 
                 ui.label("Filter Release");
-                ui.add(widgets::ParamSlider::for_param(
-                    &params.filter_release,
-                    setter,
-                ));
+                param_slider(ui, setter, midi, &params.filter_release);
 
                 ui.label("Envelope Attack");
-                ui.add(widgets::ParamSlider::for_param(&params.env_attack, setter));
+                param_slider(ui, setter, midi, &params.env_attack);
 
                 ui.label("Envelope Decay");
-                ui.add(widgets::ParamSlider::for_param(&params.env_decay, setter));
+                param_slider(ui, setter, midi, &params.env_decay);
 
                 ui.label("Envelope Sustain");
-                ui.add(widgets::ParamSlider::for_param(&params.env_sustain, setter));
+                param_slider(ui, setter, midi, &params.env_sustain);
 
                 ui.label("Envelope Release");
-                ui.add(widgets::ParamSlider::for_param(&params.env_release, setter));
+                param_slider(ui, setter, midi, &params.env_release);
 
                 ui.label("LFO Rate");
-                ui.add(widgets::ParamSlider::for_param(&params.lfo_rate, setter));
+                param_slider(ui, setter, midi, &params.lfo_rate);
 
                 ui.label("Vibrato");
-                ui.add(widgets::ParamSlider::for_param(&params.vibrato, setter));
+                param_slider(ui, setter, midi, &params.vibrato);
+
+                ui.label("Mod Wheel Vibrato");
+                param_slider(ui, setter, midi, &params.mod_wheel_vibrato);
+
+                ui.label("Mod Wheel Cutoff");
+                param_slider(ui, setter, midi, &params.mod_wheel_cutoff);
+
+                ui.label("Mod Wheel PWM");
+                param_slider(ui, setter, midi, &params.mod_wheel_pwm);
+
+                ui.label("Aftertouch Vibrato");
+                param_slider(ui, setter, midi, &params.aftertouch_vibrato);
+
+                ui.label("Aftertouch Cutoff");
+                param_slider(ui, setter, midi, &params.aftertouch_cutoff);
+
+                ui.label("Aftertouch Cutoff LFO");
+                param_slider(ui, setter, midi, &params.aftertouch_cutoff_lfo);
+
+                ui.label("Aftertouch Volume");
+                param_slider(ui, setter, midi, &params.aftertouch_volume);
+
+                ui.label("Bend Up");
+                param_slider(ui, setter, midi, &params.bend_up);
+
+                ui.label("Bend Down");
+                param_slider(ui, setter, midi, &params.bend_down);
+
+                ui.label("Bend Smoothing");
+                param_slider(ui, setter, midi, &params.bend_smoothing);
+
+                ui.horizontal(|ui| {
+                    ui.label("MPE Zone");
+                    enum_selector(
+                        ui,
+                        setter,
+                        midi,
+                        &params.mpe_zone,
+                        &[
+                            (MpeZone::Off, "Off"),
+                            (MpeZone::Lower, "Lower"),
+                            (MpeZone::Upper, "Upper"),
+                        ],
+                    );
+                });
+                ui.end_row();
+
+                ui.label("MPE Member Channels");
+                param_slider(ui, setter, midi, &params.mpe_members);
+
+                ui.label("MPE Bend Range");
+                param_slider(ui, setter, midi, &params.mpe_bend_range);
+
+                ui.label("MPE Timbre Cutoff");
+                param_slider(ui, setter, midi, &params.mpe_timbre_cutoff);
+
+                ui.label("Pan");
+                param_slider(ui, setter, midi, &params.pan);
+
+                ui.label("Key Pan");
+                param_slider(ui, setter, midi, &params.key_pan);
+
+                ui.label("Pan Spread");
+                param_slider(ui, setter, midi, &params.pan_spread);
+
+                ui.horizontal(|ui| {
+                    ui.label("Pan Spread Mode");
+                    enum_selector(
+                        ui,
+                        setter,
+                        midi,
+                        &params.pan_spread_mode,
+                        &[(PanSpreadMode::Alternate, "Alternate"), (PanSpreadMode::Random, "Random")],
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Stereo Width");
+                param_slider(ui, setter, midi, &params.stereo_width);
 
                 ui.label("Noise");
-                ui.add(widgets::ParamSlider::for_param(&params.noise_level, setter));
+                param_slider(ui, setter, midi, &params.noise_level);
 
                 ui.label("Octave");
-                ui.add(widgets::ParamSlider::for_param(&params.octave, setter));
+                param_slider(ui, setter, midi, &params.octave);
 
                 ui.label("Tuning");
-                ui.add(widgets::ParamSlider::for_param(&params.tuning, setter));
+                param_slider(ui, setter, midi, &params.tuning);
+
+                ui.label("Distortion Drive");
+                param_slider(ui, setter, midi, &params.dist_drive);
+
+                ui.label("Distortion Mix");
+                param_slider(ui, setter, midi, &params.dist_mix);
+
+                ui.label("Phaser Rate");
+                param_slider(ui, setter, midi, &params.phaser_rate);
+
+                ui.label("Phaser Depth");
+                param_slider(ui, setter, midi, &params.phaser_depth);
+
+                ui.label("Phaser Feedback");
+                param_slider(ui, setter, midi, &params.phaser_feedback);
+
+                ui.label("Phaser Mix");
+                param_slider(ui, setter, midi, &params.phaser_mix);
+
+                ui.label("EQ Low");
+                param_slider(ui, setter, midi, &params.eq_low);
+
+                ui.label("EQ Mid");
+                param_slider(ui, setter, midi, &params.eq_mid);
+
+                ui.label("EQ Mid Freq");
+                param_slider(ui, setter, midi, &params.eq_mid_freq);
+
+                ui.label("EQ High");
+                param_slider(ui, setter, midi, &params.eq_high);
+
+                ui.horizontal(|ui| {
+                    ui.label("Chorus");
+                    enum_selector(
+                        ui,
+                        setter,
+                        midi,
+                        &params.chorus_mode,
+                        &[
+                            (ChorusMode::Off, "Off"),
+                            (ChorusMode::One, "I"),
+                            (ChorusMode::Two, "II"),
+                            (ChorusMode::Both, "I+II"),
+                            (ChorusMode::Free, "Free"),
+                        ],
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Chorus Rate");
+                param_slider(ui, setter, midi, &params.chorus_rate);
+
+                ui.label("Chorus Depth");
+                param_slider(ui, setter, midi, &params.chorus_depth);
+
+                ui.label("Chorus Mix");
+                param_slider(ui, setter, midi, &params.chorus_mix);
+
+                ui.horizontal(|ui| {
+                    ui.label("Delay");
+                    enum_selector(
+                        ui,
+                        setter,
+                        midi,
+                        &params.delay_mode,
+                        &[
+                            (DelayMode::Off, "Off"),
+                            (DelayMode::Stereo, "Stereo"),
+                            (DelayMode::PingPong, "Ping-Pong"),
+                        ],
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Delay Time");
+                param_slider(ui, setter, midi, &params.delay_time);
+
+                ui.label("Delay Sync");
+                param_slider(ui, setter, midi, &params.delay_sync);
+
+                ui.label("Delay Division");
+                param_slider(ui, setter, midi, &params.delay_division);
+
+                ui.label("Delay Feedback");
+                param_slider(ui, setter, midi, &params.delay_feedback);
+
+                ui.label("Delay Low Cut");
+                param_slider(ui, setter, midi, &params.delay_low_cut);
+
+                ui.label("Delay High Cut");
+                param_slider(ui, setter, midi, &params.delay_high_cut);
+
+                ui.label("Delay Mix");
+                param_slider(ui, setter, midi, &params.delay_mix);
+
+                ui.label("Reverb Size");
+                param_slider(ui, setter, midi, &params.reverb_size);
+
+                ui.label("Reverb Decay");
+                param_slider(ui, setter, midi, &params.reverb_decay);
+
+                ui.label("Reverb Damping");
+                param_slider(ui, setter, midi, &params.reverb_damping);
+
+                ui.label("Reverb Pre-Delay");
+                param_slider(ui, setter, midi, &params.reverb_pre_delay);
+
+                ui.label("Reverb Mix");
+                param_slider(ui, setter, midi, &params.reverb_mix);
+
+                ui.label("Limiter");
+                param_slider(ui, setter, midi, &params.limiter);
+
+                ui.label("Arpeggiator");
+                param_slider(ui, setter, midi, &params.arp_enabled);
+
+                ui.horizontal(|ui| {
+                    ui.label("Arp Mode");
+                    enum_selector(
+                        ui,
+                        setter,
+                        midi,
+                        &params.arp_mode,
+                        &[
+                            (ArpMode::Up, "Up"),
+                            (ArpMode::Down, "Down"),
+                            (ArpMode::UpDown, "Up/Down"),
+                            (ArpMode::Random, "Random"),
+                            (ArpMode::AsPlayed, "As Played"),
+                            (ArpMode::Chord, "Chord"),
+                        ],
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Arp Octaves");
+                param_slider(ui, setter, midi, &params.arp_octaves);
+
+                ui.label("Arp Rate");
+                param_slider(ui, setter, midi, &params.arp_rate);
+
+                ui.label("Arp Gate");
+                param_slider(ui, setter, midi, &params.arp_gate);
+
+                ui.label("Arp Swing");
+                param_slider(ui, setter, midi, &params.arp_swing);
+
+                ui.label("Arp Latch");
+                param_slider(ui, setter, midi, &params.arp_latch);
+
+                ui.label("Sequencer");
+                param_slider(ui, setter, midi, &params.seq_enabled);
+
+                ui.label("Seq Steps");
+                param_slider(ui, setter, midi, &params.seq_steps);
+
+                ui.label("Seq Rate");
+                param_slider(ui, setter, midi, &params.seq_rate);
+
+                ui.label("Seq Accent");
+                param_slider(ui, setter, midi, &params.seq_accent);
+
+                ui.label("Chord Memory");
+                param_slider(ui, setter, midi, &params.chord_memory);
+
+                ui.horizontal(|ui| {
+                    if chord_learn.is_armed() {
+                        if ui.button("Cancel").clicked() {
+                            chord_learn.cancel();
+                        }
+                        ui.label("Play a chord and let go of the keys");
+                        ui.ctx().request_repaint();
+                    } else {
+                        if ui.button("Learn Chord").clicked() {
+                            chord_learn.arm();
+                        }
+
+                        let chord = params.chord.read();
+                        if chord.is_empty() {
+                            ui.label("No chord");
+                        } else {
+                            let intervals: Vec<String> =
+                                chord.iter().map(|interval| interval.to_string()).collect();
+                            ui.label(format!("Chord: {}", intervals.join(" ")));
+                        }
+                    }
+                });
+                ui.end_row();
 
                 // ui.label("Volume");
                 // ui.add(widgets::ParamSlider::for_param(
@@ -306,22 +1058,73 @@ fn synth_view(
     });// END CENTRAL PANEL
 }
 
-fn load_preset(preset: &Preset, setter: &ParamSetter, params: &RX11Params) {
+// A row of selectable labels, one for each of the given enum values
+fn enum_selector<T: Enum + PartialEq + Clone>(
+    ui: &mut egui::Ui,
+    setter: &ParamSetter,
+    midi: &MidiContext,
+    param: &EnumParam<T>,
+    options: &[(T, &str)],
+) {
+    let current = param.value();
+
+    for (option, label) in options {
+        let response = ui.add(egui::widgets::SelectableLabel::new(current == *option, *label));
+        response.context_menu(|ui| midi_learn_menu(ui, midi, param));
+        if response.clicked() {
+            setter.begin_set_parameter(param);
+            setter.set_parameter(param, option.clone());
+            setter.end_set_parameter(param);
+        }
+    }
+}
+
+pub fn load_preset(preset: &Preset, mut target: impl PresetTarget, params: &RX11Params) {
     for (param_name, param_value) in &preset.values {
         if &param_name[..] == "glide_mode" {
-            setter.begin_set_parameter(&params.glide_mode);
-            setter.set_parameter(
-                &params.glide_mode,
-                GlideMode::from_f32(*param_value),
-            );
-            setter.end_set_parameter(&params.glide_mode);
+            target.set_param(&params.glide_mode, GlideMode::from_f32(*param_value));
         } else if &param_name[..] == "poly_mode" {
-            setter.begin_set_parameter(&params.poly_mode);
-            setter.set_parameter(
-                &params.poly_mode,
-                PolyMode::from_f32(*param_value),
-            );
-            setter.end_set_parameter(&params.poly_mode);
+            target.set_param(&params.poly_mode, PolyMode::from_f32(*param_value));
+        } else if &param_name[..] == "voice_steal" {
+            target.set_param(&params.voice_steal, VoiceStealMode::from_f32(*param_value));
+        } else if &param_name[..] == "note_priority" {
+            target.set_param(&params.note_priority, NotePriority::from_f32(*param_value));
+        } else if &param_name[..] == "mono_retrigger" {
+            target.set_param(&params.mono_retrigger, *param_value >= 0.5);
+        } else if &param_name[..] == "glide_type" {
+            target.set_param(&params.glide_type, GlideType::from_f32(*param_value));
+        } else if &param_name[..] == "glide_sync" {
+            target.set_param(&params.glide_sync, *param_value >= 0.5);
+        } else if &param_name[..] == "glide_division" {
+            target.set_param(&params.glide_division, NoteDivision::from_f32(*param_value));
+        } else if &param_name[..] == "polyphony" {
+            target.set_param(&params.polyphony, *param_value as i32);
+        } else if let Some(slot) = fx_slot(params, param_name, "fx_type_") {
+            target.set_param(&slot.fx_type, FxType::from_f32(*param_value));
+        } else if let Some(slot) = fx_slot(params, param_name, "fx_bypass_") {
+            target.set_param(&slot.bypass, *param_value >= 0.5);
+        } else if &param_name[..] == "chorus_mode" {
+            target.set_param(&params.chorus_mode, ChorusMode::from_f32(*param_value));
+        } else if &param_name[..] == "delay_mode" {
+            target.set_param(&params.delay_mode, DelayMode::from_f32(*param_value));
+        } else if &param_name[..] == "delay_sync" {
+            target.set_param(&params.delay_sync, *param_value >= 0.5);
+        } else if &param_name[..] == "delay_division" {
+            target.set_param(&params.delay_division, NoteDivision::from_f32(*param_value));
+        } else if &param_name[..] == "pan_spread_mode" {
+            target.set_param(&params.pan_spread_mode, PanSpreadMode::from_f32(*param_value));
+        } else if &param_name[..] == "arp" {
+            target.set_param(&params.arp_enabled, *param_value >= 0.5);
+        } else if &param_name[..] == "arp_mode" {
+            target.set_param(&params.arp_mode, ArpMode::from_f32(*param_value));
+        } else if &param_name[..] == "arp_octaves" {
+            target.set_param(&params.arp_octaves, *param_value as i32);
+        } else if &param_name[..] == "arp_rate" {
+            target.set_param(&params.arp_rate, NoteDivision::from_f32(*param_value));
+        } else if &param_name[..] == "aftertouch_cutoff_lfo" {
+            target.set_param(&params.aftertouch_cutoff_lfo, *param_value >= 0.5);
+        } else if &param_name[..] == "arp_latch" {
+            target.set_param(&params.arp_latch, *param_value >= 0.5);
         } else {
             let param = match &param_name[..] {
                 "osc_mix" => Some(&params.osc_mix),
@@ -329,6 +1132,7 @@ fn load_preset(preset: &Preset, setter: &ParamSetter, params: &RX11Params) {
                 "osc_fine_tune" => Some(&params.osc_fine_tune),
                 "glide_rate" => Some(&params.glide_rate),
                 "glide_bend" => Some(&params.glide_bend),
+                "glide_time" => Some(&params.glide_time),
                 "filter_freq" => Some(&params.filter_freq),
                 "filter_reso" => Some(&params.filter_reso),
                 "filter_env" => Some(&params.filter_env),
@@ -348,14 +1152,64 @@ fn load_preset(preset: &Preset, setter: &ParamSetter, params: &RX11Params) {
                 "octave" => Some(&params.octave),
                 "tuning" => Some(&params.tuning),
                 "output" => Some(&params.output_level),
+                "pan" => Some(&params.pan),
+                "mod_wheel_vibrato" => Some(&params.mod_wheel_vibrato),
+                "mod_wheel_cutoff" => Some(&params.mod_wheel_cutoff),
+                "mod_wheel_pwm" => Some(&params.mod_wheel_pwm),
+                "aftertouch_vibrato" => Some(&params.aftertouch_vibrato),
+                "aftertouch_cutoff" => Some(&params.aftertouch_cutoff),
+                "aftertouch_volume" => Some(&params.aftertouch_volume),
+                "bend_up" => Some(&params.bend_up),
+                "bend_down" => Some(&params.bend_down),
+                "bend_smoothing" => Some(&params.bend_smoothing),
+                "chorus_rate" => Some(&params.chorus_rate),
+                "chorus_depth" => Some(&params.chorus_depth),
+                "chorus_mix" => Some(&params.chorus_mix),
+                "delay_time" => Some(&params.delay_time),
+                "delay_feedback" => Some(&params.delay_feedback),
+                "delay_low_cut" => Some(&params.delay_low_cut),
+                "delay_high_cut" => Some(&params.delay_high_cut),
+                "delay_mix" => Some(&params.delay_mix),
+                "key_pan" => Some(&params.key_pan),
+                "pan_spread" => Some(&params.pan_spread),
+                "stereo_width" => Some(&params.stereo_width),
+                "reverb_size" => Some(&params.reverb_size),
+                "reverb_decay" => Some(&params.reverb_decay),
+                "reverb_damping" => Some(&params.reverb_damping),
+                "reverb_pre_delay" => Some(&params.reverb_pre_delay),
+                "reverb_mix" => Some(&params.reverb_mix),
+                "dist_drive" => Some(&params.dist_drive),
+                "dist_mix" => Some(&params.dist_mix),
+                "phaser_rate" => Some(&params.phaser_rate),
+                "phaser_depth" => Some(&params.phaser_depth),
+                "phaser_feedback" => Some(&params.phaser_feedback),
+                "phaser_mix" => Some(&params.phaser_mix),
+                "eq_low" => Some(&params.eq_low),
+                "eq_mid" => Some(&params.eq_mid),
+                "eq_mid_freq" => Some(&params.eq_mid_freq),
+                "eq_high" => Some(&params.eq_high),
+                "arp_gate" => Some(&params.arp_gate),
+                "arp_swing" => Some(&params.arp_swing),
                 _ => None,
             };
 
             if let Some(param) = param {
-                setter.begin_set_parameter(param);
-                setter.set_parameter(param, *param_value);
-                setter.end_set_parameter(param);
+                target.set_param(param, *param_value);
             }
         }
     }
 }
+
+// The FX slot a preset column like fx_type_1 belongs to, the slots are numbered from 1
+fn fx_slot<'a>(params: &'a RX11Params, param_name: &str, prefix: &str) -> Option<&'a FxSlotParams> {
+    let number = param_name.strip_prefix(prefix)?.parse::<usize>().ok()?;
+    params.fx_slots.get(number.checked_sub(1)?)
+}
+
+impl PresetTarget for &ParamSetter<'_> {
+    fn set_param<P: Param>(&mut self, param: &P, value: P::Plain) {
+        self.begin_set_parameter(param);
+        self.set_parameter(param, value);
+        self.end_set_parameter(param);
+    }
+}
@@ -15,11 +15,73 @@ pub struct Voice {
     pub pan_right: f32,
     pub target_period: f32,
     pub glide_rate: f32,
+    /// Per update period multiplier for constant time and rate glides
+    pub glide_ratio: f32,
     pub cutoff_freq: f32,
     pub filter_resonance: f32,
     pub filter_mod: f32,
     pub filter_env_depth: f32,
+    /// Added to the filter envelope depth for accented notes
+    pub accent: f32,
     pub pitch_bend: f32,
+    /// MIDI channel the note was played on, used for per note expression
+    pub channel: u8,
+    /// The note the voice was started with, `note` changes when it is released
+    pub key: i32,
+    /// Host voice ID, or one made up from the note and channel. Cleared once the voice is done.
+    pub voice_id: Option<i32>,
+    /// A voice ID that was replaced before the host was told the voice terminated
+    pub stolen_voice_id: Option<(i32, u8, i32)>,
+    /// Polyphonic aftertouch, squared like the channel pressure
+    pub pressure: f32,
+    /// Smoothed gain offset from the per note pressure
+    pub volume_mod: f32,
+    /// Normalized CLAP polyphonic modulation offsets, indexed by poly modulation ID
+    pub poly_mod: [f32; crate::NUM_POLY_MOD_PARAMS],
+    /// The poly modulation offsets converted to the synth's units
+    pub cutoff_mod: f32,
+    pub resonance_mod: f32,
+    pub osc_mix_mod: f32,
+    pub detune_mod: f32,
+    pub pan_mod: f32,
+    /// Note expressions, tuning in semitones and brightness in -1..1
+    pub tuning: f32,
+    pub brightness: f32,
+    pub vibrato: f32,
+    pub note_gain: f32,
+    pub note_pan: f32,
+    /// Pan position from the note number, and the offset on top of it
+    pub key_pan: f32,
+    pub pan_offset: f32,
+    /// Side of the stereo field the pan spread puts the voice on, -1..1
+    pub spread_pan: f32,
+    /// Duo mode plays a second note on oscillator 2
+    pub duo: bool,
+    pub duo_period: f32,
+    pub duo_target_period: f32,
+    /// Glide ratio of the duo note, which moves by its own interval
+    pub duo_glide_ratio: f32,
+    /// Paraphonic mode only uses the oscillators of a gated voice
+    pub gated: bool,
+    /// The key was let go but a pedal keeps the note going
+    pub sustained: bool,
+    /// The key was held when the sostenuto pedal went down
+    pub sostenuto: bool,
+    /// A half pressed sustain pedal is letting the note fade out
+    pub damped: bool,
+    /// When the voice was started, used to find the oldest voice
+    pub age: u64,
+    steal_note: i32,
+    steal_velocity: f32,
+    steal_channel: u8,
+    steal_voice_id: Option<i32>,
+    steal_accent: f32,
+    steal_released: bool,
+    steal_fade_step: f32,
+    steal_fade_level: f32,
+    /// Paraphonic slot left ringing by the last phrase, fading out under the new one
+    slot_fade_step: f32,
+    slot_fade_level: f32,
     pub oscillator_1: Oscillator,
     pub oscillator_2: Oscillator,
     pub envelope: Envelope,
@@ -33,6 +95,20 @@ impl Voice {
         self.saw = 0.0;
         self.pan_left = 0.707;
         self.pan_right = 0.707;
+        self.age = 0;
+        self.voice_id = None;
+        self.stolen_voice_id = None;
+        self.key_pan = 0.0;
+        self.spread_pan = 0.0;
+        self.volume_mod = 0.0;
+        self.accent = 0.0;
+        self.duo = false;
+        self.gated = false;
+        self.sustained = false;
+        self.sostenuto = false;
+        self.damped = false;
+        self.slot_fade_step = 0.0;
+        self.cancel_steal();
 
         self.oscillator_1.reset();
         self.oscillator_2.reset();
@@ -43,22 +119,102 @@ impl Voice {
 
     // Mixes the oscillator, noise, and envelope together
     pub fn render(&mut self, input: f32) -> f32 {
+        let mut output = self.render_oscillators() + input;
+        output = self.filter.render(output);
+
+        let envelope = self.envelope.next_value();
+        output *= envelope * (1.0 + self.volume_mod) * self.note_gain;
+
+        if self.is_stealing() {
+            self.steal_fade_level = (self.steal_fade_level - self.steal_fade_step).max(0.0);
+            output *= self.steal_fade_level;
+        }
+
+        output
+        //envelope // Return only the envelope to view it in an oscilloscope
+    }
+
+    // The raw oscillator output before the filter and envelope
+    pub fn render_oscillators(&mut self) -> f32 {
         let sample_1 = self.oscillator_1.next_sample();
         let sample_2 = self.oscillator_2.next_sample();
 
         // This is a leaky integrator to create a sawtooth wave
         self.saw = self.saw * 0.997 + sample_1 - sample_2;
+        self.saw
+    }
 
-        let mut output = self.saw + input;
-        output = self.filter.render(output);
+    // The oscillators of a paraphonic slot, which fade out once the slot is let go of
+    pub fn render_slot(&mut self) -> f32 {
+        let output = self.render_oscillators();
+        if self.slot_fade_step == 0.0 {
+            return output;
+        }
 
-        let envelope = self.envelope.next_value();
-        output * envelope
-        //envelope // Return only the envelope to view it in an oscilloscope
+        self.slot_fade_level = (self.slot_fade_level - self.slot_fade_step).max(0.0);
+        if self.slot_fade_level == 0.0 {
+            self.slot_fade_step = 0.0;
+            self.gated = false;
+        }
+
+        output * self.slot_fade_level
+    }
+
+    // Lets a paraphonic slot that is still ringing fade out quickly instead of cutting it off
+    pub fn fade_out_slot(&mut self, fade_step: f32) {
+        self.slot_fade_step = fade_step;
+        self.slot_fade_level = 1.0;
+    }
+
+    // Clears the per note expression and modulation of the previous note
+    pub fn begin_note(&mut self, channel: u8, voice_id: Option<i32>, accent: f32, note: i32) {
+        if let Some(previous_voice_id) = self.voice_id {
+            self.stolen_voice_id = Some((previous_voice_id, self.channel, self.key));
+        }
+
+        self.channel = channel;
+        self.key = note;
+        self.accent = accent;
+        self.voice_id = Some(voice_id.unwrap_or(note | ((channel as i32) << 16)));
+        self.slot_fade_step = 0.0;
+
+        self.poly_mod = [0.0; crate::NUM_POLY_MOD_PARAMS];
+        self.cutoff_mod = 0.0;
+        self.resonance_mod = 0.0;
+        self.osc_mix_mod = 0.0;
+        self.detune_mod = 0.0;
+        self.pan_mod = 0.0;
+
+        self.pressure = 0.0;
+        self.tuning = 0.0;
+        self.brightness = 0.0;
+        self.vibrato = 0.0;
+        self.note_gain = 1.0;
+        self.note_pan = 0.0;
+    }
+
+    // Period multiplier for the per note pitch bend and tuning
+    #[inline(always)]
+    pub fn note_bend(&self, channel_bend: f32) -> f32 {
+        (-0.05776226505 * (channel_bend + self.tuning)).exp()
     }
 
     pub fn update_lfo(&mut self) {
-        self.period += self.glide_rate * (self.target_period - self.period);
+        self.period = Self::glide(
+            self.period,
+            self.target_period,
+            self.glide_ratio,
+            self.glide_rate,
+        );
+
+        if self.duo {
+            self.duo_period = Self::glide(
+                self.duo_period,
+                self.duo_target_period,
+                self.duo_glide_ratio,
+                self.glide_rate,
+            );
+        }
 
         let filter_env = self.filter_envelope.next_value();
 
@@ -71,13 +227,134 @@ impl Voice {
             .update_coefficients(modulated_cutoff, self.filter_resonance);
     }
 
+    fn glide(period: f32, target_period: f32, glide_ratio: f32, glide_rate: f32) -> f32 {
+        if glide_ratio > 0.0 {
+            // Linear in pitch, stops exactly at the target
+            if period < target_period {
+                (period * glide_ratio).min(target_period)
+            } else {
+                (period / glide_ratio).max(target_period)
+            }
+        } else {
+            period + glide_rate * (target_period - period)
+        }
+    }
+
+    pub fn update_period(&mut self, pitch_bend: f32, detune: f32) {
+        self.oscillator_1.period = self.period * pitch_bend;
+
+        if self.duo {
+            self.oscillator_2.period = self.duo_period * pitch_bend * detune;
+        } else {
+            self.oscillator_2.period = self.oscillator_1.period * detune;
+        }
+    }
+
     pub fn release(&mut self) {
         self.envelope.release();
         self.filter_envelope.release();
     }
 
-    pub fn update_panning(&mut self) {
-        let panning = ((self.note as f32 - 60.0) / 24.0).clamp(-1.0, 1.0);
+    // Fades the note out with a longer release while the sustain pedal is half down, 1.0 holds it
+    pub fn damp(&mut self, release_multiplier: f32) {
+        self.envelope.release_multiplier = release_multiplier;
+        self.envelope.release();
+        self.damped = true;
+    }
+
+    // A pedal let go of the note
+    pub fn release_pedal(&mut self, release_multiplier: f32) {
+        self.sustained = false;
+        self.sostenuto = false;
+        self.damped = false;
+        self.envelope.release_multiplier = release_multiplier;
+        self.release();
+        self.note = 0;
+    }
+
+    // Fades the voice out quickly before it gets restarted with the new note. The voice keeps the
+    // key, voice ID and modulation of the old note until the fade is done.
+    pub fn steal(
+        &mut self,
+        note: i32,
+        velocity: f32,
+        channel: u8,
+        voice_id: Option<i32>,
+        accent: f32,
+        fade_step: f32,
+    ) {
+        self.note = 0;
+        self.steal_note = note;
+        self.steal_velocity = velocity;
+        self.steal_channel = channel;
+        self.steal_voice_id = voice_id;
+        self.steal_accent = accent;
+        self.steal_released = false;
+        self.steal_fade_step = fade_step;
+        self.steal_fade_level = 1.0;
+    }
+
+    #[inline(always)]
+    pub fn is_stealing(&self) -> bool {
+        self.steal_fade_step > 0.0
+    }
+
+    // A released note can go silent before the fade gets to the end
+    #[inline(always)]
+    pub fn is_steal_complete(&self) -> bool {
+        self.is_stealing() && (self.steal_fade_level <= 0.0 || !self.envelope.is_active())
+    }
+
+    /// Remembers a note off that arrived while the voice was still fading out, `None` matches any channel
+    pub fn release_steal(&mut self, note: i32, channel: Option<u8>) {
+        if self.is_stealing()
+            && self.steal_note == note
+            && channel.is_none_or(|channel| channel == self.steal_channel)
+        {
+            self.steal_released = true;
+        }
+    }
+
+    /// Releases the stolen note whichever it is, it stops as soon as it has started
+    pub fn release_stolen_note(&mut self) {
+        if self.is_stealing() {
+            self.steal_released = true;
+        }
+    }
+
+    pub fn cancel_steal(&mut self) {
+        self.steal_note = 0;
+        self.steal_velocity = 0.0;
+        self.steal_channel = 0;
+        self.steal_voice_id = None;
+        self.steal_accent = 0.0;
+        self.steal_released = false;
+        self.steal_fade_step = 0.0;
+        self.steal_fade_level = 0.0;
+    }
+
+    // Clears the faded out voice so the stolen note starts from silence.
+    // Returns the note, velocity and whether the note was already released.
+    pub fn finish_steal(&mut self) -> (i32, f32, bool) {
+        let steal = (self.steal_note, self.steal_velocity, self.steal_released);
+        self.begin_note(self.steal_channel, self.steal_voice_id, self.steal_accent, self.steal_note);
+        self.cancel_steal();
+
+        self.envelope.reset();
+        self.filter_envelope.reset();
+        self.filter.reset();
+
+        steal
+    }
+
+    /// At full key pan amount two octaves away from middle C is panned all the way
+    pub fn update_panning(&mut self, key_pan_amount: f32) {
+        self.key_pan = (key_pan_amount * (self.note as f32 - 60.0) / 24.0).clamp(-1.0, 1.0);
+        self.update_pan_gains();
+    }
+
+    pub fn update_pan_gains(&mut self) {
+        let panning = (self.key_pan + self.pan_offset).clamp(-1.0, 1.0);
         self.pan_left = (PI_OVER_FOUR * (1.0 - panning)).sin();
         self.pan_right = (PI_OVER_FOUR * (1.0 + panning)).sin();
     }